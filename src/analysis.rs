@@ -0,0 +1,635 @@
+//! Semantic analysis for EventQL.
+//!
+//! This module checks that a parsed [`Query`] is meaningful and annotates it
+//! with type information:
+//!
+//! - every variable used in an expression must be bound by a FROM clause of
+//!   the query it appears in, and a query cannot bind the same name twice;
+//! - every expression gets its [`Type`] inferred and stored in
+//!   [`Attrs::tpe`](crate::Attrs::tpe), and expressions used in an
+//!   incompatible way are rejected.
+//!
+//! # Main Function
+//!
+//! - [`analyze`] - Resolve names and infer types of a Query AST
+use crate::ast::{App, Expr, GroupBy, Query, SourceKind, Type, Value};
+use crate::error::AnalysisError;
+use crate::functions::FunctionRegistry;
+use crate::schema::{EVENTS_SOURCE, event_envelope, event_envelope_with_data};
+use crate::token::Operator;
+use std::collections::{BTreeMap, HashMap};
+
+/// Result type for analysis operations.
+///
+/// This is a convenience alias for `Result<T, AnalysisError>`.
+pub type AnalysisResult<A> = Result<A, AnalysisError>;
+
+/// Types known within a single query.
+///
+/// Entries are keyed by access path: the variables bound by the FROM clauses
+/// (`e`) but also the fields reached through them (`e.data.price`), whose type
+/// is learnt from the way they are used.
+#[derive(Default)]
+struct Scope {
+    paths: HashMap<String, Type>,
+}
+
+/// Options controlling semantic analysis.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::Type;
+/// use eventql_parser::prelude::AnalysisOptions;
+/// use std::collections::BTreeMap;
+///
+/// let mut options = AnalysisOptions::default();
+/// options.register_data_schema(
+///     "io.eventsourcingdb.library.book-acquired",
+///     Type::Record(BTreeMap::from([
+///         ("title".to_string(), Type::String),
+///         ("total".to_string(), Type::Number),
+///     ])),
+/// );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisOptions {
+    /// Shape of the `data` payload of events, keyed by event type
+    pub data_schemas: HashMap<String, Type>,
+    /// Functions that can be called from a query
+    pub functions: FunctionRegistry,
+}
+
+impl AnalysisOptions {
+    /// Register the shape of the `data` payload of events of the given type.
+    ///
+    /// When a query filters its events on that type (e.g.
+    /// `WHERE e.type == "io.eventsourcingdb.library.book-acquired"`), the
+    /// analysis types `e.data` with `schema` instead of [`Type::Any`].
+    pub fn register_data_schema(
+        &mut self,
+        event_type: impl Into<String>,
+        schema: Type,
+    ) -> &mut Self {
+        self.data_schemas.insert(event_type.into(), schema);
+        self
+    }
+}
+
+struct Analysis<'a> {
+    options: &'a AnalysisOptions,
+    scopes: HashMap<u64, Scope>,
+}
+
+impl<'a> Analysis<'a> {
+    fn new(options: &'a AnalysisOptions) -> Self {
+        Self {
+            options,
+            scopes: HashMap::new(),
+        }
+    }
+
+    /// Returns the type of the events bound to `binding`.
+    ///
+    /// If the query only keeps events of a single type with a registered data
+    /// schema, the `data` payload of those events is typed with that schema.
+    fn event_type(&self, predicate: Option<&Expr>, binding: &str) -> Type {
+        predicate
+            .and_then(|predicate| filtered_event_type(predicate, binding))
+            .and_then(|event_type| self.options.data_schemas.get(event_type))
+            .map(|schema| event_envelope_with_data(schema.clone()))
+            .unwrap_or_else(event_envelope)
+    }
+
+    fn analyze_query(&mut self, query: &mut Query) -> AnalysisResult<()> {
+        let mut scope = Scope::default();
+
+        let predicate = query.predicate.as_ref();
+
+        for source in &mut query.sources {
+            let tpe = match &mut source.kind {
+                SourceKind::Name(name) if name == EVENTS_SOURCE => {
+                    self.event_type(predicate, &source.binding)
+                }
+                SourceKind::Name(_) => Type::Any,
+                SourceKind::Subject(_) => self.event_type(predicate, &source.binding),
+                SourceKind::Subquery(subquery) => {
+                    self.analyze_query(subquery)?;
+                    subquery.attrs.tpe.clone()
+                }
+            };
+
+            if scope.paths.insert(source.binding.clone(), tpe).is_some() {
+                return Err(AnalysisError::DuplicateBinding(
+                    source.pos.line,
+                    source.pos.col,
+                    source.binding.clone(),
+                ));
+            }
+        }
+
+        self.scopes.insert(query.attrs.scope, scope);
+
+        if let Some(predicate) = &mut query.predicate {
+            self.check_expr(predicate, &Type::Bool)?;
+        }
+
+        if let Some(group_by) = &mut query.group_by {
+            let tpe = self.infer_expr(&mut group_by.expr)?;
+
+            if let Some(binding) = &group_by.binding
+                && let Some(scope) = self.scopes.get_mut(&query.attrs.scope)
+                && scope.paths.insert(binding.clone(), tpe).is_some()
+            {
+                return Err(AnalysisError::DuplicateBinding(
+                    group_by.expr.attrs.pos.line,
+                    group_by.expr.attrs.pos.col,
+                    binding.clone(),
+                ));
+            }
+
+            if let Some(predicate) = &mut group_by.predicate {
+                self.check_expr(predicate, &Type::Bool)?;
+            }
+        }
+
+        if let Some(order_by) = &mut query.order_by {
+            self.infer_expr(&mut order_by.expr)?;
+        }
+
+        query.attrs.tpe = self.infer_expr(&mut query.projection)?;
+        self.scopes.remove(&query.attrs.scope);
+
+        self.check_aggregates(query)
+    }
+
+    /// Enforce where aggregate functions can be used within a query.
+    ///
+    /// A query aggregates its rows when it has a GROUP BY clause or when its
+    /// projection calls an aggregate function, in which case all its rows form
+    /// a single group. The projection, HAVING and ORDER BY clauses of such a
+    /// query can only refer to the rows through the grouping expression, the
+    /// name bound to it, or through aggregate functions. Aggregates are never
+    /// allowed in WHERE or GROUP BY clauses, nor as arguments of other
+    /// aggregates.
+    fn check_aggregates(&self, query: &Query) -> AnalysisResult<()> {
+        if let Some(predicate) = &query.predicate {
+            self.check_no_aggregate(predicate)?;
+        }
+
+        if let Some(group_by) = &query.group_by {
+            self.check_no_aggregate(&group_by.expr)?;
+        } else if !self.options.functions.has_aggregate(&query.projection) {
+            if let Some(order_by) = &query.order_by {
+                self.check_no_aggregate(&order_by.expr)?;
+            }
+
+            return self.check_no_aggregate(&query.projection);
+        }
+
+        let group_by = query.group_by.as_ref();
+
+        if let Some(predicate) = group_by.and_then(|group_by| group_by.predicate.as_ref()) {
+            self.check_grouped(predicate, group_by)?;
+        }
+
+        if let Some(order_by) = &query.order_by {
+            self.check_grouped(&order_by.expr, group_by)?;
+        }
+
+        self.check_grouped(&query.projection, group_by)
+    }
+
+    fn aggregate<'b>(&self, expr: &'b Expr) -> Option<&'b App> {
+        if let Value::App(app) = &expr.value
+            && self.options.functions.is_aggregate(&app.func)
+        {
+            return Some(app);
+        }
+
+        None
+    }
+
+    fn check_no_aggregate(&self, expr: &Expr) -> AnalysisResult<()> {
+        if let Some(app) = self.aggregate(expr) {
+            return Err(AnalysisError::UnexpectedAggregate(
+                expr.attrs.pos.line,
+                expr.attrs.pos.col,
+                app.func.clone(),
+            ));
+        }
+
+        expr.children()
+            .try_for_each(|child| self.check_no_aggregate(child))
+    }
+
+    /// Make sure an expression only refers to rows through the group key or aggregates.
+    fn check_grouped(&self, expr: &Expr, group_by: Option<&GroupBy>) -> AnalysisResult<()> {
+        if let Some(group_by) = group_by {
+            if same_expr(expr, &group_by.expr) {
+                return Ok(());
+            }
+
+            if let Value::Id(name) = &expr.value
+                && group_by.binding.as_ref() == Some(name)
+            {
+                return Ok(());
+            }
+        }
+
+        if let Some(app) = self.aggregate(expr) {
+            return app
+                .args
+                .iter()
+                .try_for_each(|arg| self.check_no_aggregate(arg));
+        }
+
+        if let Value::Id(name) = &expr.value {
+            return Err(AnalysisError::UngroupedExpr(
+                expr.attrs.pos.line,
+                expr.attrs.pos.col,
+                name.clone(),
+            ));
+        }
+
+        expr.children()
+            .try_for_each(|child| self.check_grouped(child, group_by))
+    }
+
+    /// Infer the type of an expression and make sure it is compatible with `expected`.
+    ///
+    /// If the type of the expression is less precise than `expected`, it is
+    /// refined and remembered for later uses of the same path.
+    fn check_expr(&mut self, expr: &mut Expr, expected: &Type) -> AnalysisResult<()> {
+        self.infer_expr(expr)?;
+        self.refine(expr, expected)
+    }
+
+    fn refine(&mut self, expr: &mut Expr, expected: &Type) -> AnalysisResult<()> {
+        let Some(tpe) = expected.unify(&expr.attrs.tpe) else {
+            return Err(AnalysisError::TypeMismatch(
+                expr.attrs.pos.line,
+                expr.attrs.pos.col,
+                expected.clone(),
+                expr.attrs.tpe.clone(),
+            ));
+        };
+
+        if tpe == expr.attrs.tpe {
+            return Ok(());
+        }
+
+        expr.attrs.tpe = tpe.clone();
+
+        if let Value::Group(inner) = &mut expr.value {
+            return self.refine(inner, &tpe);
+        }
+
+        if let Some(path) = access_path(expr)
+            && let Some(scope) = self.scopes.get_mut(&expr.attrs.scope)
+        {
+            scope.paths.insert(path, tpe);
+        }
+
+        Ok(())
+    }
+
+    fn infer_expr(&mut self, expr: &mut Expr) -> AnalysisResult<Type> {
+        let known = access_path(expr).and_then(|path| {
+            self.scopes
+                .get(&expr.attrs.scope)
+                .and_then(|scope| scope.paths.get(&path).cloned())
+        });
+
+        let tpe = match &mut expr.value {
+            Value::Number(_) => Type::Number,
+            Value::String(_) => Type::String,
+            Value::Bool(_) => Type::Bool,
+
+            Value::Id(name) => match known {
+                Some(tpe) => tpe,
+                None => {
+                    return Err(AnalysisError::UnknownVariable(
+                        expr.attrs.pos.line,
+                        expr.attrs.pos.col,
+                        name.clone(),
+                    ));
+                }
+            },
+
+            Value::Array(elems) => {
+                let mut elem_tpe = Type::Unspecified;
+
+                for elem in elems.iter_mut() {
+                    self.check_expr(elem, &elem_tpe)?;
+                    elem_tpe = elem.attrs.tpe.clone();
+                }
+
+                Type::Array(Box::new(elem_tpe))
+            }
+
+            Value::Record(fields) => {
+                let mut tpes = BTreeMap::new();
+
+                for field in fields.iter_mut() {
+                    tpes.insert(field.name.clone(), self.infer_expr(&mut field.value)?);
+                }
+
+                Type::Record(tpes)
+            }
+
+            Value::Access(access) => {
+                let target = self.infer_expr(&mut access.target)?;
+                let field = field_type(&target, &access.field).ok_or_else(|| match &target {
+                    Type::Record(_) | Type::Optional(_) => AnalysisError::UnknownField(
+                        expr.attrs.pos.line,
+                        expr.attrs.pos.col,
+                        access.field.clone(),
+                        target.clone(),
+                    ),
+                    _ => AnalysisError::ExpectedRecord(
+                        expr.attrs.pos.line,
+                        expr.attrs.pos.col,
+                        target.clone(),
+                    ),
+                })?;
+
+                known.unwrap_or(field)
+            }
+
+            Value::App(app) => {
+                let Some(sig) = self.options.functions.lookup(&app.func) else {
+                    return Err(AnalysisError::UnknownFunction(
+                        expr.attrs.pos.line,
+                        expr.attrs.pos.col,
+                        app.func.clone(),
+                    ));
+                };
+
+                if sig.args.len() != app.args.len() {
+                    return Err(AnalysisError::WrongArity(
+                        expr.attrs.pos.line,
+                        expr.attrs.pos.col,
+                        app.func.clone(),
+                        sig.args.len(),
+                        app.args.len(),
+                    ));
+                }
+
+                for (arg, tpe) in app.args.iter_mut().zip(&sig.args) {
+                    self.check_expr(arg, tpe)?;
+                }
+
+                sig.result.clone()
+            }
+
+            Value::Binary(binary) => match binary.operator {
+                Operator::Add
+                | Operator::Sub
+                | Operator::Mul
+                | Operator::Div
+                | Operator::Mod
+                | Operator::Pow => {
+                    self.check_expr(&mut binary.lhs, &Type::Number)?;
+                    self.check_expr(&mut binary.rhs, &Type::Number)?;
+
+                    Type::Number
+                }
+
+                Operator::Eq
+                | Operator::Neq
+                | Operator::Lt
+                | Operator::Lte
+                | Operator::Gt
+                | Operator::Gte => {
+                    let lhs = self.infer_expr(&mut binary.lhs)?;
+                    self.check_expr(&mut binary.rhs, &lhs)?;
+                    let rhs = binary.rhs.attrs.tpe.clone();
+                    self.refine(&mut binary.lhs, &rhs)?;
+
+                    Type::Bool
+                }
+
+                Operator::And | Operator::Or | Operator::Xor | Operator::Not => {
+                    self.check_expr(&mut binary.lhs, &Type::Bool)?;
+                    self.check_expr(&mut binary.rhs, &Type::Bool)?;
+
+                    Type::Bool
+                }
+            },
+
+            Value::Unary(unary) => {
+                let tpe = if let Operator::Not = unary.operator {
+                    Type::Bool
+                } else {
+                    Type::Number
+                };
+
+                self.check_expr(&mut unary.expr, &tpe)?;
+
+                tpe
+            }
+
+            Value::Group(inner) => self.infer_expr(inner)?,
+        };
+
+        expr.attrs.tpe = tpe.clone();
+
+        Ok(tpe)
+    }
+}
+
+/// Returns the type of `field` when accessed on a value of type `target`.
+///
+/// Fields of dynamic values are themselves dynamic, while accessing a field
+/// through an optional record yields an optional value.
+fn field_type(target: &Type, field: &str) -> Option<Type> {
+    match target {
+        Type::Unspecified | Type::Any => Some(Type::Any),
+        Type::Record(fields) => fields.get(field).cloned(),
+        Type::Optional(inner) => field_type(inner, field).map(|tpe| match tpe {
+            Type::Optional(_) => tpe,
+            tpe => Type::Optional(Box::new(tpe)),
+        }),
+        _ => None,
+    }
+}
+
+/// Returns true if both expressions are structurally equal, regardless of
+/// their attributes and of redundant parentheses.
+fn same_expr(a: &Expr, b: &Expr) -> bool {
+    match (&a.value, &b.value) {
+        (Value::Group(a), _) => same_expr(a, b),
+        (_, Value::Group(b)) => same_expr(a, b),
+        (Value::Number(a), Value::Number(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Id(a), Value::Id(b)) => a == b,
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_expr(a, b))
+        }
+        (Value::Record(a), Value::Record(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| a.name == b.name && same_expr(&a.value, &b.value))
+        }
+        (Value::Access(a), Value::Access(b)) => {
+            a.field == b.field && same_expr(&a.target, &b.target)
+        }
+        (Value::App(a), Value::App(b)) => {
+            a.func.eq_ignore_ascii_case(&b.func)
+                && a.args.len() == b.args.len()
+                && a.args.iter().zip(&b.args).all(|(a, b)| same_expr(a, b))
+        }
+        (Value::Binary(a), Value::Binary(b)) => {
+            a.operator == b.operator && same_expr(&a.lhs, &b.lhs) && same_expr(&a.rhs, &b.rhs)
+        }
+        (Value::Unary(a), Value::Unary(b)) => {
+            a.operator == b.operator && same_expr(&a.expr, &b.expr)
+        }
+        _ => false,
+    }
+}
+
+/// Returns the event type `binding` is restricted to by a predicate.
+///
+/// Only equalities such as `e.type == "..."` found at the top level of the
+/// predicate, possibly nested in conjunctions, are taken into account.
+fn filtered_event_type<'a>(predicate: &'a Expr, binding: &str) -> Option<&'a str> {
+    match &predicate.value {
+        Value::Group(inner) => filtered_event_type(inner, binding),
+
+        Value::Binary(binary) if binary.operator == Operator::And => {
+            filtered_event_type(&binary.lhs, binding)
+                .or_else(|| filtered_event_type(&binary.rhs, binding))
+        }
+
+        Value::Binary(binary) if binary.operator == Operator::Eq => {
+            match (&binary.lhs.value, &binary.rhs.value) {
+                (Value::Access(access), Value::String(event_type))
+                | (Value::String(event_type), Value::Access(access))
+                    if access.field == "type"
+                        && matches!(&access.target.value, Value::Id(name) if name == binding) =>
+                {
+                    Some(event_type)
+                }
+
+                _ => None,
+            }
+        }
+
+        _ => None,
+    }
+}
+
+/// Returns the dotted path (e.g. `e.data.price`) of a variable or field access.
+fn access_path(expr: &Expr) -> Option<String> {
+    match &expr.value {
+        Value::Id(name) => Some(name.clone()),
+        Value::Access(access) => {
+            access_path(&access.target).map(|target| format!("{}.{}", target, access.field))
+        }
+        _ => None,
+    }
+}
+
+/// Perform semantic analysis on a Query AST.
+///
+/// This function walks every clause of the query, including subqueries used as
+/// sources, and returns a copy of the query where every expression has its type
+/// inferred. Following the [`Attrs::scope`](crate::Attrs::scope) of each
+/// expression, variables bound by an outer query are not visible from within a
+/// subquery and vice versa.
+///
+/// Variables bound to the `events` source, or to a subject pattern, are typed
+/// with the [`event_envelope`] schema, so accessing a field that is not part of
+/// the envelope (e.g. `e.tyep`) is an error. The types of fields reached
+/// through a dynamic value (e.g. `e.data.price`) are learnt from their first
+/// use and must remain consistent across the query.
+/// The type of the query itself is the type of its projection.
+///
+/// The name bound to the group key of a GROUP BY clause (see
+/// [`GroupBy::binding`](crate::GroupBy::binding)) is visible in the HAVING,
+/// ORDER BY and PROJECT INTO clauses of the query and has the type of the key.
+///
+/// # Errors
+///
+/// - [`AnalysisError::UnknownVariable`] when an expression refers to an unbound name
+/// - [`AnalysisError::DuplicateBinding`] when a query binds the same name twice
+/// - [`AnalysisError::TypeMismatch`] when an expression is used with an incompatible type,
+///   such as a WHERE clause that is not a boolean
+/// - [`AnalysisError::UnknownField`] when accessing a field a record does not have
+/// - [`AnalysisError::ExpectedRecord`] when accessing a field on a value that is not a record
+/// - [`AnalysisError::UnknownFunction`] when calling a function that is not registered
+/// - [`AnalysisError::WrongArity`] when calling a function with the wrong number of arguments
+/// - [`AnalysisError::UnexpectedAggregate`] when calling an aggregate function in a WHERE
+///   or GROUP BY clause, or within another aggregate
+/// - [`AnalysisError::UngroupedExpr`] when an aggregating query refers to its rows outside
+///   of the grouping expression and of aggregate functions
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::{Type, parse_query};
+/// use eventql_parser::prelude::analyze;
+///
+/// let query = parse_query("FROM e IN events WHERE e.id == \"1\" PROJECT INTO e").unwrap();
+/// let query = analyze(&query).unwrap();
+/// assert_eq!(query.predicate.unwrap().attrs.tpe, Type::Bool);
+///
+/// let query = parse_query("FROM e IN events WHERE e.id == 1 PROJECT INTO e").unwrap();
+/// assert!(analyze(&query).is_err());
+///
+/// let query = parse_query("FROM e IN events WHERE f.id == \"1\" PROJECT INTO e").unwrap();
+/// assert!(analyze(&query).is_err());
+///
+/// let query = parse_query("FROM e IN events WHERE 42 PROJECT INTO e").unwrap();
+/// assert!(analyze(&query).is_err());
+/// ```
+pub fn analyze(query: &Query) -> AnalysisResult<Query> {
+    analyze_with_options(query, &AnalysisOptions::default())
+}
+
+/// Perform semantic analysis on a Query AST using custom options.
+///
+/// This behaves like [`analyze`] but takes the data schemas and functions
+/// registered in `options` into account: when a query only keeps events of a
+/// single type with a registered schema, the `data` payload of those events is
+/// typed with that schema instead of being dynamic.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::{Type, parse_query};
+/// use eventql_parser::prelude::{AnalysisOptions, analyze_with_options};
+/// use std::collections::BTreeMap;
+///
+/// let mut options = AnalysisOptions::default();
+/// options.register_data_schema(
+///     "io.eventsourcingdb.library.book-acquired",
+///     Type::Record(BTreeMap::from([("total".to_string(), Type::Number)])),
+/// );
+///
+/// let query = parse_query(
+///     "FROM e IN events \
+///      WHERE e.type == \"io.eventsourcingdb.library.book-acquired\" AND e.data.total > 100 \
+///      PROJECT INTO e",
+/// ).unwrap();
+/// assert!(analyze_with_options(&query, &options).is_ok());
+///
+/// let query = parse_query(
+///     "FROM e IN events \
+///      WHERE e.type == \"io.eventsourcingdb.library.book-acquired\" AND e.data.total == \"100\" \
+///      PROJECT INTO e",
+/// ).unwrap();
+/// assert!(analyze_with_options(&query, &options).is_err());
+/// ```
+pub fn analyze_with_options(query: &Query, options: &AnalysisOptions) -> AnalysisResult<Query> {
+    let mut analysis = Analysis::new(options);
+    let mut query = query.clone();
+
+    analysis.analyze_query(&mut query)?;
+
+    Ok(query)
+}
@@ -11,8 +11,11 @@
 //! - [`Value`] - The various kinds of expression values (literals, operators, etc.)
 //! - [`Source`] - Data sources in FROM clauses
 //!
+use crate::lexer::is_ident;
 use crate::token::{Operator, Token};
 use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 
 /// Position information for source code locations.
 ///
@@ -49,29 +52,128 @@ impl From<Token<'_>> for Pos {
 ///
 /// This enum represents the type of an expression in the EventQL type system.
 /// Types can be inferred during semantic analysis or left as `Unspecified`.
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize)]
+///
+/// Container types are parameterised: an array knows the type of its elements
+/// and a record knows the type of each of its fields, which lets the shape of a
+/// projection flow through subqueries.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::Type;
+/// use std::collections::BTreeMap;
+///
+/// let order = Type::Record(BTreeMap::from([
+///     ("id".to_string(), Type::String),
+///     ("price".to_string(), Type::Number),
+///     ("tags".to_string(), Type::Array(Box::new(Type::String))),
+/// ]));
+///
+/// assert_eq!(order.to_string(), "{id: string, price: number, tags: [string]}");
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
 pub enum Type {
     /// Type has not been determined yet
     Unspecified,
+    /// Dynamic type, for values without a known schema (e.g. `e.data`)
+    Any,
     /// Numeric type (f64)
     Number,
     /// String type
     String,
     /// Boolean type
     Bool,
-    /// Array type
-    Array,
-    /// Record (object) type
-    Record,
+    /// Array type with the type of its elements
+    Array(Box<Type>),
+    /// Record (object) type with the type of each of its fields
+    Record(BTreeMap<String, Type>),
+    /// Type of values that may be missing or null
+    Optional(Box<Type>),
     /// Subject pattern type
     Subject,
 }
 
+impl Type {
+    /// Find the most precise type compatible with both `self` and `other`.
+    ///
+    /// `Unspecified` and `Any` are compatible with every type and give way to the
+    /// other, more precise, type. Combining a type with its optional counterpart
+    /// yields the optional type. Returns `None` if the two types are incompatible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use eventql_parser::Type;
+    ///
+    /// assert_eq!(Type::Any.unify(&Type::Number), Some(Type::Number));
+    /// assert_eq!(
+    ///     Type::Optional(Box::new(Type::Number)).unify(&Type::Number),
+    ///     Some(Type::Optional(Box::new(Type::Number)))
+    /// );
+    /// assert_eq!(Type::String.unify(&Type::Number), None);
+    /// ```
+    pub fn unify(&self, other: &Type) -> Option<Type> {
+        match (self, other) {
+            (Type::Unspecified, tpe) | (tpe, Type::Unspecified) => Some(tpe.clone()),
+            (Type::Any, tpe) | (tpe, Type::Any) => Some(tpe.clone()),
+            (Type::Optional(a), Type::Optional(b)) => {
+                a.unify(b).map(|tpe| Type::Optional(Box::new(tpe)))
+            }
+            (Type::Optional(a), b) | (b, Type::Optional(a)) => {
+                a.unify(b).map(|tpe| Type::Optional(Box::new(tpe)))
+            }
+            (Type::Array(a), Type::Array(b)) => a.unify(b).map(|tpe| Type::Array(Box::new(tpe))),
+            (Type::Record(a), Type::Record(b)) => {
+                if a.len() != b.len() {
+                    return None;
+                }
+
+                let mut fields = BTreeMap::new();
+                for (name, tpe) in a {
+                    fields.insert(name.clone(), tpe.unify(b.get(name)?)?);
+                }
+
+                Some(Type::Record(fields))
+            }
+            (a, b) if a == b => Some(a.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Unspecified => write!(f, "unspecified"),
+            Type::Any => write!(f, "any"),
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Bool => write!(f, "bool"),
+            Type::Array(elem) => write!(f, "[{}]", elem),
+            Type::Record(fields) => {
+                write!(f, "{{")?;
+
+                for (idx, (name, tpe)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}: {}", name, tpe)?;
+                }
+
+                write!(f, "}}")
+            }
+            Type::Optional(tpe) => write!(f, "{}?", tpe),
+            Type::Subject => write!(f, "subject"),
+        }
+    }
+}
+
 /// Attributes attached to each expression node.
 ///
 /// These attributes provide metadata about an expression, including its
 /// position in the source code, scope information, and type information.
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Attrs {
     /// Source position of this expression
     pub pos: Pos,
@@ -105,6 +207,130 @@ pub struct Expr {
     pub value: Value,
 }
 
+impl Expr {
+    /// Returns the direct subexpressions of this expression.
+    pub fn children(&self) -> Box<dyn Iterator<Item = &Expr> + '_> {
+        match &self.value {
+            Value::Number(_) | Value::String(_) | Value::Bool(_) | Value::Id(_) => {
+                Box::new(std::iter::empty())
+            }
+            Value::Array(elems) => Box::new(elems.iter()),
+            Value::Record(fields) => Box::new(fields.iter().map(|field| &field.value)),
+            Value::Access(access) => Box::new(std::iter::once(access.target.as_ref())),
+            Value::App(app) => Box::new(app.args.iter()),
+            Value::Binary(binary) => {
+                Box::new([binary.lhs.as_ref(), binary.rhs.as_ref()].into_iter())
+            }
+            Value::Unary(unary) => Box::new(std::iter::once(unary.expr.as_ref())),
+            Value::Group(inner) => Box::new(std::iter::once(inner.as_ref())),
+        }
+    }
+}
+
+/// Writes a string as a double-quoted EventQL literal, escaping the
+/// characters that cannot appear as is.
+pub(crate) struct Quoted<'a>(pub(crate) &'a str);
+
+impl Display for Quoted<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "\"")?;
+
+        for c in self.0.chars() {
+            match c {
+                '"' => write!(f, "\\\"")?,
+                '\\' => write!(f, "\\\\")?,
+                '\n' => write!(f, "\\n")?,
+                '\r' => write!(f, "\\r")?,
+                '\t' => write!(f, "\\t")?,
+                c if c.is_control() => write!(f, "\\u{{{:x}}}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+
+        write!(f, "\"")
+    }
+}
+
+/// Writes a name as an EventQL identifier, quoting it with backticks if it
+/// cannot be written as is.
+pub(crate) struct Ident<'a>(pub(crate) &'a str);
+
+impl Display for Ident<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if is_ident(self.0) {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "`{}`", self.0)
+        }
+    }
+}
+
+impl Display for Expr {
+    /// Writes the expression back in EventQL syntax.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", Quoted(s)),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Id(name) => write!(f, "{}", Ident(name)),
+            Value::Array(elems) => {
+                write!(f, "[")?;
+
+                for (idx, elem) in elems.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", elem)?;
+                }
+
+                write!(f, "]")
+            }
+            Value::Record(fields) => {
+                write!(f, "{{")?;
+
+                for (idx, field) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}: {}", Ident(&field.name), field.value)?;
+                }
+
+                write!(f, "}}")
+            }
+            Value::Access(access) => write!(f, "{}.{}", access.target, Ident(&access.field)),
+            Value::App(app) => {
+                write!(f, "{}(", app.func)?;
+
+                for (idx, arg) in app.args.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", arg)?;
+                }
+
+                write!(f, ")")
+            }
+            Value::Binary(binary) => {
+                write!(f, "{} {} {}", binary.lhs, binary.operator, binary.rhs)
+            }
+            Value::Unary(unary) if unary.operator == Operator::Not => {
+                write!(f, "NOT {}", unary.expr)
+            }
+            Value::Unary(unary) => {
+                // `- -x` must not read as a `--` comment.
+                let operand = unary.expr.to_string();
+                let sep = if operand.starts_with('-') { " " } else { "" };
+
+                write!(f, "{}{}{}", unary.operator, sep, operand)
+            }
+            Value::Group(inner) => write!(f, "({})", inner),
+        }
+    }
+}
+
 /// Field access expression (e.g., `e.data.price`).
 ///
 /// Represents accessing a field of a record or object using dot notation.
@@ -224,6 +450,8 @@ pub enum Value {
 /// - `kind`: `SourceKind::Name("events")`
 #[derive(Debug, Clone, Serialize)]
 pub struct Source {
+    /// Source position of the binding
+    pub pos: Pos,
     /// Variable name bound to this source
     pub binding: String,
     /// What this source represents
@@ -274,19 +502,43 @@ pub enum Order {
     Desc,
 }
 
+impl Display for Order {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Order::Asc => write!(f, "ASC"),
+            Order::Desc => write!(f, "DESC"),
+        }
+    }
+}
+
 /// GROUP BY clause specification
 ///
-/// Defines how query results should be order by.
+/// Defines how query results should be grouped.
+///
+/// The group key can be given a name, either explicitly with `AS` or
+/// automatically from the last field of an access expression. That name is
+/// bound to the value of the group key in the HAVING, ORDER BY and PROJECT INTO
+/// clauses of the query.
+///
 /// # Examples
 ///
 /// In `GROUP BY e.age HAVING age > 123`, this would be represented as:
 /// - `expr`: expression for `e.age`
+/// - `binding`: `Some("age")`, named after the last field of `e.age`
 /// - `predicate`: `age > 123`
+///
+/// In `GROUP BY e.data.age + 1 AS nextAge`, `binding` is `Some("nextAge")`.
+/// Without `AS`, a group key that is not a field access, or whose last field
+/// is already bound by a source of the query (e.g., `GROUP BY e.data.e`), has
+/// no name.
 #[derive(Debug, Clone, Serialize)]
 pub struct GroupBy {
     /// Expression to group by
     pub expr: Expr,
 
+    /// Name bound to the group key, if any
+    pub binding: Option<String>,
+
     /// Predicate to filter groups after aggregation
     pub predicate: Option<Expr>,
 }
@@ -309,6 +561,15 @@ pub enum Limit {
     Top(u64),
 }
 
+impl Display for Limit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Skip(n) => write!(f, "SKIP {}", n),
+            Limit::Top(n) => write!(f, "TOP {}", n),
+        }
+    }
+}
+
 /// A complete EventQL query.
 ///
 /// This is the root node of the AST, representing a full query with all its clauses.
@@ -320,7 +581,7 @@ pub enum Limit {
 /// FROM <alias> <source>
 /// [FROM <alias> <source>] ...
 /// [WHERE <condition>]
-/// [GROUP BY <field> [HAVING <condition>]]
+/// [GROUP BY <field> [AS <alias>] [HAVING <condition>]]
 /// [ORDER BY <field> ASC|DESC]
 /// [TOP|SKIP <n>]
 /// PROJECT INTO [DISTINCT] <projection>
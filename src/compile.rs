@@ -0,0 +1,357 @@
+//! Compilation of expressions into native closures.
+//!
+//! The interpreter of the [`eval`](crate::eval) module walks the expression tree
+//! for every row it evaluates. This module instead turns an expression into a
+//! tree of closures once: field paths such as `e.data.price` are resolved into
+//! a list of field names, and operators and functions are dispatched ahead of
+//! time. The resulting closure can then be called on many events, for instance
+//! to filter events as they are ingested.
+//!
+//! Compiled expressions follow the semantics of the interpreter and refer to a
+//! single variable, bound to the event the closure is called with.
+//!
+//! # Main Functions
+//!
+//! - [`compile_predicate`] - Compile a predicate into a closure
+//! - [`compile_projection`] - Compile a projection into a closure
+use crate::ast::{Expr, Pos, Value};
+use crate::error::EvalError;
+use crate::eval::{
+    EvalResult, arithmetic, arithmetic_op, as_f64, builtins, compare, comparison, number, ordered,
+    scalar, type_mismatch,
+};
+use crate::token::Operator;
+use serde_json::{Map, Value as JsonValue};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// Compiled expression. Values read from the event are borrowed rather than
+/// copied.
+type Program = Box<dyn for<'e> Fn(&'e JsonValue) -> EvalResult<Cow<'e, JsonValue>> + Send + Sync>;
+
+/// Compiled predicate.
+type Test = Box<dyn Fn(&JsonValue) -> EvalResult<bool> + Send + Sync>;
+
+static NULL: JsonValue = JsonValue::Null;
+
+/// Compile a predicate, such as the WHERE clause of a query, into a closure
+/// telling whether an event satisfies it.
+///
+/// As in a query, a `null` result does not satisfy the predicate.
+///
+/// # Errors
+///
+/// - [`EvalError::UnknownVariable`] when the expression refers to more than one variable
+/// - [`EvalError::UnexpectedAggregate`] when the expression uses an aggregate function
+///
+/// The closure reports the errors the interpreter would report for the event,
+/// such as [`EvalError::TypeMismatch`].
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::compile_predicate;
+/// use serde_json::json;
+///
+/// let query = parse_query(
+///     r#"FROM e IN events WHERE e.type == "book-acquired" AND e.data.price > 20 PROJECT INTO e"#
+/// ).unwrap();
+/// let predicate = compile_predicate(query.predicate.as_ref().unwrap()).unwrap();
+///
+/// assert!(predicate(&json!({ "type": "book-acquired", "data": { "price": 42 } })).unwrap());
+/// assert!(!predicate(&json!({ "type": "book-acquired", "data": { "price": 10 } })).unwrap());
+/// assert!(!predicate(&json!({ "type": "book-borrowed" })).unwrap());
+/// ```
+pub fn compile_predicate(
+    expr: &Expr,
+) -> EvalResult<impl Fn(&JsonValue) -> EvalResult<bool> + Send + Sync + 'static> {
+    Compiler::default().predicate(expr)
+}
+
+/// Compile a projection, such as the PROJECT INTO clause of a query, into a
+/// closure computing its value for an event.
+///
+/// # Errors
+///
+/// - [`EvalError::UnknownVariable`] when the expression refers to more than one variable
+/// - [`EvalError::UnexpectedAggregate`] when the expression uses an aggregate function
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::compile_projection;
+/// use serde_json::json;
+///
+/// let query = parse_query(
+///     "FROM e IN events PROJECT INTO { id: e.id, total: e.data.price * e.data.quantity }"
+/// ).unwrap();
+/// let projection = compile_projection(&query.projection).unwrap();
+/// let event = json!({ "id": "1", "data": { "price": 5, "quantity": 3 } });
+///
+/// assert_eq!(projection(&event).unwrap(), json!({ "id": "1", "total": 15 }));
+/// ```
+pub fn compile_projection(
+    expr: &Expr,
+) -> EvalResult<impl Fn(&JsonValue) -> EvalResult<JsonValue> + Send + Sync + 'static> {
+    let program = Compiler::default().expr(expr)?;
+
+    Ok(move |event: &JsonValue| program(event).map(Cow::into_owned))
+}
+
+/// Forces the signature of a closure to the one of [`Program`].
+fn program<F>(f: F) -> Program
+where
+    F: for<'e> Fn(&'e JsonValue) -> EvalResult<Cow<'e, JsonValue>> + Send + Sync + 'static,
+{
+    Box::new(f)
+}
+
+#[derive(Default)]
+struct Compiler {
+    binding: Option<String>,
+}
+
+impl Compiler {
+    fn predicate(&mut self, expr: &Expr) -> EvalResult<Test> {
+        let program = self.expr(expr)?;
+        let pos = expr.attrs.pos;
+
+        Ok(Box::new(move |event| match program(event)?.as_ref() {
+            JsonValue::Bool(value) => Ok(*value),
+            JsonValue::Null => Ok(false),
+            value => Err(type_mismatch(pos, "bool", value)),
+        }))
+    }
+
+    fn expr(&mut self, expr: &Expr) -> EvalResult<Program> {
+        let pos = expr.attrs.pos;
+
+        match &expr.value {
+            Value::Number(n) => Ok(constant(number(*n))),
+            Value::String(s) => Ok(constant(JsonValue::String(s.clone()))),
+            Value::Bool(b) => Ok(constant(JsonValue::Bool(*b))),
+
+            Value::Id(name) => {
+                self.bind(pos, name)?;
+                Ok(program(|event| Ok(Cow::Borrowed(event))))
+            }
+
+            Value::Array(elems) => {
+                let elems = elems
+                    .iter()
+                    .map(|elem| self.expr(elem))
+                    .collect::<EvalResult<Vec<_>>>()?;
+
+                Ok(program(move |event| {
+                    elems
+                        .iter()
+                        .map(|elem| elem(event).map(Cow::into_owned))
+                        .collect::<EvalResult<Vec<_>>>()
+                        .map(|elems| Cow::Owned(JsonValue::Array(elems)))
+                }))
+            }
+
+            Value::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|field| Ok((field.name.clone(), self.expr(&field.value)?)))
+                    .collect::<EvalResult<Vec<_>>>()?;
+
+                Ok(program(move |event| {
+                    let mut record = Map::new();
+
+                    for (name, value) in &fields {
+                        record.insert(name.clone(), value(event)?.into_owned());
+                    }
+
+                    Ok(Cow::Owned(JsonValue::Object(record)))
+                }))
+            }
+
+            Value::Access(_) => self.access(expr),
+
+            Value::App(app) => {
+                if builtins().is_aggregate(&app.func) {
+                    return Err(EvalError::UnexpectedAggregate(
+                        pos.line,
+                        pos.col,
+                        app.func.clone(),
+                    ));
+                }
+
+                let func = app.func.clone();
+                let args = app
+                    .args
+                    .iter()
+                    .map(|arg| self.expr(arg))
+                    .collect::<EvalResult<Vec<_>>>()?;
+
+                Ok(program(move |event| {
+                    let args = args
+                        .iter()
+                        .map(|arg| arg(event).map(Cow::into_owned))
+                        .collect::<EvalResult<Vec<_>>>()?;
+
+                    scalar(pos, &func, args).map(Cow::Owned)
+                }))
+            }
+
+            Value::Binary(binary) => match binary.operator {
+                Operator::And | Operator::Or | Operator::Xor => {
+                    let lhs = self.predicate(&binary.lhs)?;
+                    let rhs = self.predicate(&binary.rhs)?;
+                    let result = |value| Ok(Cow::Owned(JsonValue::Bool(value)));
+
+                    Ok(match binary.operator {
+                        Operator::And => program(move |event| result(lhs(event)? && rhs(event)?)),
+                        Operator::Or => program(move |event| result(lhs(event)? || rhs(event)?)),
+                        _ => program(move |event| result(lhs(event)? != rhs(event)?)),
+                    })
+                }
+
+                operator => {
+                    let lhs = self.expr(&binary.lhs)?;
+                    let rhs = self.expr(&binary.rhs)?;
+
+                    Ok(match operator {
+                        Operator::Eq | Operator::Neq => {
+                            let expected = operator == Operator::Eq;
+
+                            program(move |event| {
+                                let equal = compare(lhs(event)?.as_ref(), rhs(event)?.as_ref())
+                                    == Ordering::Equal;
+                                Ok(Cow::Owned(JsonValue::Bool(equal == expected)))
+                            })
+                        }
+
+                        Operator::Lt | Operator::Lte | Operator::Gt | Operator::Gte => {
+                            let test = comparison(operator);
+
+                            program(move |event| {
+                                ordered(pos, test, lhs(event)?.as_ref(), rhs(event)?.as_ref())
+                                    .map(Cow::Owned)
+                            })
+                        }
+
+                        _ => {
+                            let op = arithmetic_op(operator);
+
+                            program(move |event| {
+                                arithmetic(pos, op, lhs(event)?.as_ref(), rhs(event)?.as_ref())
+                                    .map(Cow::Owned)
+                            })
+                        }
+                    })
+                }
+            },
+
+            Value::Unary(unary) => {
+                let operand = self.expr(&unary.expr)?;
+                let operand_pos = unary.expr.attrs.pos;
+
+                Ok(match unary.operator {
+                    Operator::Not => program(move |event| match operand(event)?.as_ref() {
+                        JsonValue::Null => Ok(Cow::Owned(JsonValue::Null)),
+                        JsonValue::Bool(b) => Ok(Cow::Owned(JsonValue::Bool(!b))),
+                        value => Err(type_mismatch(operand_pos, "bool", value)),
+                    }),
+
+                    operator => {
+                        let negate = operator == Operator::Sub;
+
+                        program(move |event| {
+                            let value = operand(event)?;
+
+                            match value.as_ref() {
+                                JsonValue::Null => Ok(value),
+                                JsonValue::Number(n) if negate => {
+                                    Ok(Cow::Owned(number(-as_f64(n))))
+                                }
+                                JsonValue::Number(_) => Ok(value),
+                                other => Err(type_mismatch(operand_pos, "number", other)),
+                            }
+                        })
+                    }
+                })
+            }
+
+            Value::Group(inner) => self.expr(inner),
+        }
+    }
+
+    /// Compiles a field access. Chains of accesses starting from the variable,
+    /// such as `e.data.price`, are resolved into a path walked without copying
+    /// intermediate records.
+    fn access(&mut self, expr: &Expr) -> EvalResult<Program> {
+        let mut path = Vec::new();
+        let mut target = expr;
+
+        while let Value::Access(access) = &target.value {
+            path.push((access.field.clone(), target.attrs.pos));
+            target = &access.target;
+        }
+
+        path.reverse();
+
+        if let Value::Id(name) = &target.value {
+            self.bind(target.attrs.pos, name)?;
+
+            return Ok(program(move |event| {
+                let mut value = event;
+
+                for (field, pos) in &path {
+                    value = match value {
+                        JsonValue::Object(record) => record.get(field).unwrap_or(&NULL),
+                        JsonValue::Null => return Ok(Cow::Borrowed(&NULL)),
+                        value => return Err(type_mismatch(*pos, "record", value)),
+                    };
+                }
+
+                Ok(Cow::Borrowed(value))
+            }));
+        }
+
+        let target = self.expr(target)?;
+
+        Ok(program(move |event| {
+            let mut value = target(event)?;
+
+            for (field, pos) in &path {
+                value = match value {
+                    Cow::Borrowed(JsonValue::Object(record)) => {
+                        Cow::Borrowed(record.get(field).unwrap_or(&NULL))
+                    }
+                    Cow::Owned(JsonValue::Object(mut record)) => {
+                        Cow::Owned(record.remove(field).unwrap_or(JsonValue::Null))
+                    }
+                    value if value.is_null() => return Ok(Cow::Borrowed(&NULL)),
+                    value => return Err(type_mismatch(*pos, "record", &value)),
+                };
+            }
+
+            Ok(value)
+        }))
+    }
+
+    /// Binds the event to the first variable the expression refers to.
+    fn bind(&mut self, pos: Pos, name: &str) -> EvalResult<()> {
+        match &self.binding {
+            None => {
+                self.binding = Some(name.to_owned());
+                Ok(())
+            }
+            Some(binding) if binding == name => Ok(()),
+            Some(_) => Err(EvalError::UnknownVariable(
+                pos.line,
+                pos.col,
+                name.to_owned(),
+            )),
+        }
+    }
+}
+
+fn constant(value: JsonValue) -> Program {
+    program(move |_| Ok(Cow::Owned(value.clone())))
+}
@@ -0,0 +1,276 @@
+//! Index constraints for EventQL.
+//!
+//! Event stores index the envelope of events, typically by `type`, `subject`
+//! and `time`. This module summarizes the conditions a predicate sets on these
+//! fields, so that a storage layer can choose an index scan without
+//! understanding arbitrary expressions.
+//!
+//! The summary is conjunctive: an event satisfies the predicate if and only if
+//! it satisfies every constraint and the residual predicate. Conditions that do
+//! not translate into a constraint are kept in the residual predicate.
+//!
+//! # Main Function
+//!
+//! - [`extract_constraints`] - Extract index constraints from a predicate
+use crate::ast::{Expr, Value};
+use crate::optimize::{and, conjuncts};
+use crate::token::Operator;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+/// Constraints a predicate sets on the envelope of events, see
+/// [`extract_constraints`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Constraints {
+    /// Types events must have (e.g., from `e.type == "a" OR e.type == "b"`),
+    /// any type if `None`. An empty set matches no event
+    pub types: Option<BTreeSet<String>>,
+    /// Prefix subjects must start with (e.g., from `STARTSWITH(e.subject, "/books")`),
+    /// any subject if `None`
+    pub subject_prefix: Option<String>,
+    /// Range times must lie within (e.g., from `e.time >= "2024-01-01"`)
+    pub time: TimeRange,
+    /// Conditions not covered by the constraints, if any
+    pub residual: Option<Expr>,
+}
+
+/// Range of event times.
+///
+/// Like the interpreter, times are compared as strings, which orders
+/// timestamps written in the same format (e.g., RFC 3339) chronologically.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TimeRange {
+    /// Lower bound of the range
+    pub start: Bound<String>,
+    /// Upper bound of the range
+    pub end: Bound<String>,
+}
+
+impl Default for TimeRange {
+    fn default() -> Self {
+        Self {
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+        }
+    }
+}
+
+impl TimeRange {
+    /// Returns whether the range places no condition on times.
+    pub fn is_unbounded(&self) -> bool {
+        self.start == Bound::Unbounded && self.end == Bound::Unbounded
+    }
+
+    fn restrict_start(&mut self, bound: Bound<String>) {
+        if tighter(&bound, &self.start, |a, b| a > b) {
+            self.start = bound;
+        }
+    }
+
+    fn restrict_end(&mut self, bound: Bound<String>) {
+        if tighter(&bound, &self.end, |a, b| a < b) {
+            self.end = bound;
+        }
+    }
+}
+
+/// Returns whether a bound restricts a range more than the current one, where
+/// `further` tells whether a value lies beyond another.
+fn tighter(
+    bound: &Bound<String>,
+    current: &Bound<String>,
+    further: fn(&str, &str) -> bool,
+) -> bool {
+    match (bound, current) {
+        (Bound::Unbounded, _) => false,
+        (_, Bound::Unbounded) => true,
+        (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b))
+            if a != b =>
+        {
+            further(a, b)
+        }
+        _ => matches!((bound, current), (Bound::Excluded(_), Bound::Included(_))),
+    }
+}
+
+/// Extract the constraints a predicate sets on the `type`, `subject` and
+/// `time` fields of the events bound to a variable.
+///
+/// The following conditions, combined with AND, are recognized:
+///
+/// - `e.type == "..."`, or several of them combined with OR
+/// - `STARTSWITH(e.subject, "...")`
+/// - `e.time` compared to a string with `==`, `<`, `<=`, `>` or `>=`
+///
+/// Run [`optimize`](crate::prelude::optimize) beforehand to push the
+/// conditions of enclosing queries down to the variable scanning the events
+/// and to fold constant expressions.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::extract_constraints;
+/// use std::ops::Bound;
+///
+/// let query = parse_query(
+///     "FROM e IN events \
+///      WHERE (e.type == \"book-acquired\" OR e.type == \"book-borrowed\") \
+///      AND e.time >= \"2024-01-01\" \
+///      AND e.data.price > 20 \
+///      PROJECT INTO e"
+/// ).unwrap();
+///
+/// let constraints = extract_constraints(query.predicate.as_ref().unwrap(), "e");
+///
+/// assert_eq!(constraints.types.unwrap().len(), 2);
+/// assert_eq!(constraints.time.start, Bound::Included("2024-01-01".to_string()));
+/// assert_eq!(constraints.residual.unwrap().to_string(), "e.data.price > 20");
+/// ```
+pub fn extract_constraints(predicate: &Expr, binding: &str) -> Constraints {
+    let mut constraints = Constraints::default();
+    let mut residual = Vec::new();
+
+    for conjunct in conjuncts(predicate.clone()) {
+        if !constraints.add(&conjunct, binding) {
+            residual.push(conjunct);
+        }
+    }
+
+    constraints.residual = residual.into_iter().reduce(and);
+    constraints
+}
+
+impl Constraints {
+    /// Records the constraint expressed by a condition, returning `false` if it
+    /// does not express one.
+    fn add(&mut self, expr: &Expr, binding: &str) -> bool {
+        if let Some(types) = types(expr, binding) {
+            self.types = Some(match self.types.take() {
+                None => types,
+                Some(current) => current.intersection(&types).cloned().collect(),
+            });
+
+            return true;
+        }
+
+        if let Some(prefix) = subject_prefix(expr, binding) {
+            return match &self.subject_prefix {
+                Some(current) if current.starts_with(&prefix) => true,
+                Some(current) if !prefix.starts_with(current.as_str()) => false,
+                _ => {
+                    self.subject_prefix = Some(prefix);
+                    true
+                }
+            };
+        }
+
+        let Some((field, operator, value)) = comparison(expr, binding) else {
+            return false;
+        };
+
+        if field != "time" {
+            return false;
+        }
+
+        match operator {
+            Operator::Eq => {
+                self.time.restrict_start(Bound::Included(value.clone()));
+                self.time.restrict_end(Bound::Included(value));
+            }
+            Operator::Gt => self.time.restrict_start(Bound::Excluded(value)),
+            Operator::Gte => self.time.restrict_start(Bound::Included(value)),
+            Operator::Lt => self.time.restrict_end(Bound::Excluded(value)),
+            Operator::Lte => self.time.restrict_end(Bound::Included(value)),
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// Returns the types allowed by a condition comparing the type of events to
+/// strings, possibly several of them combined with OR.
+fn types(expr: &Expr, binding: &str) -> Option<BTreeSet<String>> {
+    match &strip(expr).value {
+        Value::Binary(binary) if binary.operator == Operator::Or => {
+            let mut allowed = types(&binary.lhs, binding)?;
+            allowed.extend(types(&binary.rhs, binding)?);
+            Some(allowed)
+        }
+
+        _ => match comparison(expr, binding)? {
+            (field, Operator::Eq, value) if field == "type" => Some(BTreeSet::from([value])),
+            _ => None,
+        },
+    }
+}
+
+/// Returns the prefix of a `STARTSWITH(e.subject, "...")` condition.
+fn subject_prefix(expr: &Expr, binding: &str) -> Option<String> {
+    let Value::App(app) = &strip(expr).value else {
+        return None;
+    };
+
+    match app.args.as_slice() {
+        [subject, prefix]
+            if app.func.eq_ignore_ascii_case("STARTSWITH")
+                && field(subject, binding)? == "subject" =>
+        {
+            string(prefix)
+        }
+        _ => None,
+    }
+}
+
+/// Splits a condition comparing an envelope field to a string into the name
+/// of the field, the operator and the string, the field being written first.
+fn comparison(expr: &Expr, binding: &str) -> Option<(String, Operator, String)> {
+    let Value::Binary(binary) = &strip(expr).value else {
+        return None;
+    };
+
+    if let (Some(field), Some(value)) = (field(&binary.lhs, binding), string(&binary.rhs)) {
+        return Some((field.to_owned(), binary.operator, value));
+    }
+
+    let operator = match binary.operator {
+        Operator::Lt => Operator::Gt,
+        Operator::Lte => Operator::Gte,
+        Operator::Gt => Operator::Lt,
+        Operator::Gte => Operator::Lte,
+        operator => operator,
+    };
+
+    Some((
+        field(&binary.rhs, binding)?.to_owned(),
+        operator,
+        string(&binary.lhs)?,
+    ))
+}
+
+/// Returns the name of the field of a `binding.field` access.
+fn field<'a>(expr: &'a Expr, binding: &str) -> Option<&'a str> {
+    match &strip(expr).value {
+        Value::Access(access) if matches!(&strip(&access.target).value, Value::Id(name) if name == binding) => {
+            Some(access.field.as_str())
+        }
+        _ => None,
+    }
+}
+
+fn string(expr: &Expr) -> Option<String> {
+    match &strip(expr).value {
+        Value::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn strip(mut expr: &Expr) -> &Expr {
+    while let Value::Group(inner) = &expr.value {
+        expr = inner;
+    }
+
+    expr
+}
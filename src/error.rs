@@ -1,25 +1,37 @@
 //! Error types for lexical analysis and parsing.
 //!
-//! This module defines the error types that can occur during tokenization
-//! and parsing of EventQL queries. All errors include position information
+//! This module defines the error types that can occur during tokenization,
+//! parsing and semantic analysis of EventQL queries. All errors include position information
 //! (line and column numbers) to help diagnose issues in query strings.
 
+use crate::ast::Type;
 use crate::token::Symbol;
 use thiserror::Error;
 
 /// Top-level error type for the EventQL parser.
 ///
-/// This enum wraps both lexer and parser errors, providing a unified
-/// error type for the entire parsing pipeline.
+/// This enum wraps lexer, parser and analysis errors, providing a unified
+/// error type for the entire pipeline. It is non-exhaustive because evaluation
+/// errors are only part of it when the `eval` feature is enabled.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// Error during lexical analysis (tokenization).
     #[error(transparent)]
-    Lexer(LexerError),
+    Lexer(#[from] LexerError),
 
     /// Error during syntactic analysis (parsing).
     #[error(transparent)]
-    Parser(ParserError),
+    Parser(#[from] ParserError),
+
+    /// Error during semantic analysis.
+    #[error(transparent)]
+    Analysis(#[from] AnalysisError),
+
+    /// Error during query evaluation.
+    #[cfg(feature = "eval")]
+    #[error(transparent)]
+    Eval(#[from] EvalError),
 }
 
 /// Errors that can occur during lexical analysis.
@@ -29,15 +41,52 @@ pub enum Error {
 #[derive(Debug, Error)]
 pub enum LexerError {
     /// The input ended unexpectedly while parsing a token.
-    ///
-    /// This typically occurs when a string literal or other multi-character
-    /// token is not properly closed.
     #[error("unexpected end of input")]
     IncompleteInput,
 
+    /// A string literal is not closed.
+    ///
+    /// Fields: `(line, column)` of the opening quote
+    #[error("{0}:{1}: unterminated string literal")]
+    UnterminatedString(u32, u32),
+
+    /// A string literal contains an unknown or malformed escape sequence
+    /// (e.g., `\q`, `\u{110000}`).
+    ///
+    /// Fields: `(line, column)` of the backslash
+    #[error("{0}:{1}: invalid escape sequence")]
+    InvalidEscape(u32, u32),
+
+    /// A number is malformed (e.g., `1e`, `12abc`) or too large.
+    ///
+    /// Fields: `(line, column)`
+    #[error("{0}:{1}: invalid number")]
+    InvalidNumber(u32, u32),
+
+    /// An identifier quoted with backticks is not closed on the line it starts.
+    ///
+    /// Fields: `(line, column)` of the opening backtick
+    #[error("{0}:{1}: unterminated quoted identifier")]
+    UnterminatedIdentifier(u32, u32),
+
+    /// A block comment is not closed.
+    ///
+    /// Fields: `(line, column)` of the opening `/*`
+    #[error("{0}:{1}: unterminated comment")]
+    UnterminatedComment(u32, u32),
+
+    /// A character cannot start any token (e.g., `#`, or `!` not followed by `=`).
+    ///
+    /// Fields: `(line, column, character)`
+    #[error("{0}:{1}: unexpected character {2:?}")]
+    UnexpectedCharacter(u32, u32, char),
+
     /// An invalid character was encountered at the specified position.
     ///
     /// The tuple contains `(line_number, column_number)`.
+    #[deprecated(
+        note = "no longer produced, the lexer reports `UnexpectedCharacter` or a more specific error instead"
+    )]
     #[error("{0}:{1}: invalid character")]
     InvalidSymbol(u32, u32),
 }
@@ -82,3 +131,127 @@ pub enum ParserError {
     #[error("unexpected end of file")]
     UnexpectedEof,
 }
+
+/// Errors that can occur during semantic analysis.
+///
+/// These errors are produced when a syntactically valid query does not make
+/// sense, for example when it refers to a variable that was never bound or
+/// compares values of incompatible types.
+#[derive(Debug, Error)]
+pub enum AnalysisError {
+    /// A variable was referenced but is not bound by any FROM clause in scope.
+    ///
+    /// Fields: `(line, column, name)`
+    #[error("{0}:{1}: unknown variable {2}")]
+    UnknownVariable(u32, u32, String),
+
+    /// The same variable name was bound twice within a query, either by its FROM
+    /// clauses or by the name given to its group key.
+    ///
+    /// Fields: `(line, column, name)`
+    #[error("{0}:{1}: variable {2} is already bound")]
+    DuplicateBinding(u32, u32, String),
+
+    /// An expression does not have the type its context requires.
+    ///
+    /// Fields: `(line, column, expected_type, found_type)`
+    #[error("{0}:{1}: expected type {2} but got {3}")]
+    TypeMismatch(u32, u32, Type, Type),
+
+    /// A field was accessed on an expression that is not a record.
+    ///
+    /// Fields: `(line, column, found_type)`
+    #[error("{0}:{1}: expected a record but got {2}")]
+    ExpectedRecord(u32, u32, Type),
+
+    /// A field that does not exist was accessed on a record.
+    ///
+    /// Fields: `(line, column, field, record_type)`
+    #[error("{0}:{1}: unknown field {2} on {3}")]
+    UnknownField(u32, u32, String, Type),
+
+    /// A function that is not part of the function registry was called.
+    ///
+    /// Fields: `(line, column, name)`
+    #[error("{0}:{1}: unknown function {2}")]
+    UnknownFunction(u32, u32, String),
+
+    /// A function was called with the wrong number of arguments.
+    ///
+    /// Fields: `(line, column, name, expected_count, found_count)`
+    #[error("{0}:{1}: function {2} expects {3} argument(s) but got {4}")]
+    WrongArity(u32, u32, String, usize, usize),
+
+    /// An aggregate function was called where aggregates are not allowed,
+    /// such as in a WHERE clause or within another aggregate.
+    ///
+    /// Fields: `(line, column, name)`
+    #[error("{0}:{1}: aggregate function {2} is not allowed here")]
+    UnexpectedAggregate(u32, u32, String),
+
+    /// A query aggregating its rows referred to a variable outside of the
+    /// grouping expression and of aggregate functions.
+    ///
+    /// Fields: `(line, column, name)`
+    #[error("{0}:{1}: {2} must be used in the GROUP BY expression or in an aggregate function")]
+    UngroupedExpr(u32, u32, String),
+}
+
+/// Errors that can occur while evaluating a query.
+///
+/// These errors are produced by the interpreter when a query cannot be run
+/// against the provided events, typically because a value does not have the
+/// type an operator or a function expects.
+#[cfg(feature = "eval")]
+#[derive(Debug, Error)]
+pub enum EvalError {
+    /// The query reads from a source the event store does not provide.
+    ///
+    /// Fields: `(name)`
+    #[error("unknown source {0}")]
+    UnknownSource(String),
+
+    /// A variable was referenced but is not bound by any FROM clause in scope.
+    ///
+    /// Fields: `(line, column, name)`
+    #[error("{0}:{1}: unknown variable {2}")]
+    UnknownVariable(u32, u32, String),
+
+    /// A function the interpreter does not implement was called, such as a
+    /// custom function registered for the analysis.
+    ///
+    /// Fields: `(line, column, name)`
+    #[error("{0}:{1}: unknown function {2}")]
+    UnknownFunction(u32, u32, String),
+
+    /// A function was called with the wrong number of arguments.
+    ///
+    /// Fields: `(line, column, name, expected_count, found_count)`
+    #[error("{0}:{1}: function {2} expects {3} argument(s) but got {4}")]
+    WrongArity(u32, u32, String, usize, usize),
+
+    /// A value does not have the type its context requires.
+    ///
+    /// Fields: `(line, column, expected_kind, found_kind)`
+    #[error("{0}:{1}: expected {2} but got {3}")]
+    TypeMismatch(u32, u32, &'static str, &'static str),
+
+    /// An aggregate function was called outside of a group of rows.
+    ///
+    /// Fields: `(line, column, name)`
+    #[error("{0}:{1}: aggregate function {2} is not allowed here")]
+    UnexpectedAggregate(u32, u32, String),
+
+    /// A number was divided by zero, with `/` or `%`.
+    ///
+    /// Fields: `(line, column)`
+    #[error("{0}:{1}: division by zero")]
+    DivisionByZero(u32, u32),
+
+    /// A plan uses an operator where it cannot be executed, such as a filter
+    /// over the results of a projection.
+    ///
+    /// Fields: `(operator)`
+    #[error("unexpected {0} operator in plan")]
+    InvalidPlan(&'static str),
+}
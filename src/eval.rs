@@ -0,0 +1,1064 @@
+//! In-memory interpreter for EventQL.
+//!
+//! This module provides a reference implementation of the semantics of
+//! EventQL: it runs a parsed [`Query`] against events represented as JSON
+//! values and returns the projected rows. It is available when the opt-in
+//! `eval` feature is enabled.
+//!
+//! A query is evaluated in the following order:
+//!
+//! 1. `FROM`: the rows are the cartesian product of all the sources
+//! 2. `WHERE`: rows for which the predicate is not `true` are discarded
+//! 3. `GROUP BY`: rows are grouped by key, then groups are filtered by `HAVING`
+//! 4. `ORDER BY`: rows, or groups, are sorted
+//! 5. `PROJECT INTO`: every row, or group, is turned into a result
+//! 6. `DISTINCT`: duplicated results are discarded
+//! 7. `TOP`/`SKIP`: the results are limited
+//!
+//! A query whose projection uses an aggregate function without a GROUP BY
+//! clause treats all its rows as a single group.
+//!
+//! Only the built-in functions of EventQL can be evaluated: functions
+//! registered in a [`FunctionRegistry`] pass semantic analysis but fail with
+//! [`EvalError::UnknownFunction`] here. `RAND()` draws from the thread-local
+//! generator of the `fastrand` crate, which `fastrand::seed` makes
+//! reproducible.
+//!
+//! Queries are [`optimize`]d and lowered into a logical [`Plan`], whose
+//! operators (scan, filter, aggregate, sort, project, distinct and limit)
+//! become a pipeline of lazy iterators pulling rows one at a time, so that
+//! memory usage stays bounded unless a GROUP BY or ORDER BY clause needs to
+//! see every row.
+//!
+//! # Main Functions
+//!
+//! - [`eval`] - Run a Query against an event store
+//! - [`eval_iter`] - Run a Query against an event store, one result at a time
+//! - [`eval_plan_iter`] - Run a logical Plan against an event store, one result at a time
+use crate::ast::{Expr, Limit, Order, Pos, Query, Value};
+use crate::error::EvalError;
+use crate::functions::FunctionRegistry;
+use crate::optimize::optimize;
+use crate::plan::{Plan, ScanSource, lower_with_functions};
+use crate::schema::EVENTS_SOURCE;
+use crate::token::Operator;
+use serde_json::{Map, Number, Value as JsonValue};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Result type for evaluation operations.
+///
+/// This is a convenience alias for `Result<T, EvalError>`.
+pub type EvalResult<A> = Result<A, EvalError>;
+
+/// Provides the events a query reads from.
+///
+/// A slice of JSON values acts as a store holding the `events` source, while a
+/// map can hold any number of named sources. Subject patterns (e.g.
+/// `FROM e IN "/books"`) read the `events` source and keep the events whose
+/// subject is the pattern itself or lies beneath it.
+pub trait EventStore {
+    /// Returns the events of the named source.
+    fn scan(&self, source: &str) -> EvalResult<Box<dyn Iterator<Item = JsonValue> + '_>>;
+}
+
+impl EventStore for [JsonValue] {
+    fn scan(&self, source: &str) -> EvalResult<Box<dyn Iterator<Item = JsonValue> + '_>> {
+        if source != EVENTS_SOURCE {
+            return Err(EvalError::UnknownSource(source.to_owned()));
+        }
+
+        Ok(Box::new(self.iter().cloned()))
+    }
+}
+
+impl EventStore for HashMap<String, Vec<JsonValue>> {
+    fn scan(&self, source: &str) -> EvalResult<Box<dyn Iterator<Item = JsonValue> + '_>> {
+        match self.get(source) {
+            Some(events) => Ok(Box::new(events.iter().cloned())),
+            None => Err(EvalError::UnknownSource(source.to_owned())),
+        }
+    }
+}
+
+/// Values bound to the variables of a query for a single row.
+type Env = HashMap<String, JsonValue>;
+
+/// Lazy sequence of values produced by an operator of the pipeline.
+type Stream<'a, A> = Box<dyn Iterator<Item = EvalResult<A>> + 'a>;
+
+/// A row flowing through the pipeline: the values bound to the variables of
+/// the query and, once rows are grouped, all the rows of its group.
+#[derive(Default)]
+struct Row {
+    env: Env,
+    group: Option<Vec<Env>>,
+}
+
+impl Row {
+    fn ctx(&self) -> Context<'_> {
+        Context {
+            env: &self.env,
+            group: self.group.as_deref(),
+        }
+    }
+}
+
+/// What an expression is evaluated against: a row and, for aggregating
+/// queries, all the rows of the group it belongs to.
+#[derive(Clone, Copy)]
+struct Context<'a> {
+    env: &'a Env,
+    group: Option<&'a [Env]>,
+}
+
+struct Evaluator<'a, S: ?Sized> {
+    store: &'a S,
+    functions: &'static FunctionRegistry,
+}
+
+impl<S: ?Sized> Clone for Evaluator<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S: ?Sized> Copy for Evaluator<'_, S> {}
+
+impl<'a, S: EventStore + ?Sized> Evaluator<'a, S> {
+    /// Executes a plan producing rows: any operator but projections and
+    /// distincts.
+    ///
+    /// Rows are pulled one at a time from the sources, so that a query such as
+    /// `FROM e IN events WHERE ... TOP 10 PROJECT INTO e` stops reading events
+    /// once it found ten matches. Only grouping and sorting need to see all the
+    /// rows before producing any.
+    ///
+    /// The returned stream does not borrow the plan: operators keep a copy of
+    /// the expressions they evaluate.
+    fn rows(self, plan: &Plan) -> Stream<'a, Row> {
+        match plan {
+            Plan::Unit => Box::new(std::iter::once(Ok(Row::default()))),
+            Plan::Scan { binding, source } => bind(binding, self.scan(source)),
+            Plan::SubqueryScan { binding, input, .. } => bind(binding, self.results(input)),
+            Plan::CrossJoin { lhs, rhs } => self.cross_join(self.rows(lhs), rhs),
+            Plan::Filter { input, predicate } => self.filter(self.rows(input), predicate),
+            Plan::Aggregate {
+                input, key: None, ..
+            } => group_all(self.rows(input)),
+            Plan::Aggregate {
+                input,
+                key: Some(key),
+                binding,
+            } => self.group(self.rows(input), key, binding),
+            Plan::Sort { input, expr, order } => self.sort(self.rows(input), expr, *order),
+            Plan::Limit { input, limit } => limit_by(self.rows(input), *limit),
+            Plan::Project { .. } => invalid_plan("Project"),
+            Plan::Distinct { .. } => invalid_plan("Distinct"),
+        }
+    }
+
+    /// Executes a plan producing results: a projection, possibly followed by
+    /// distincts and limits.
+    fn results(self, plan: &Plan) -> Stream<'a, JsonValue> {
+        match plan {
+            Plan::Project { input, expr } => self.project(self.rows(input), expr),
+            Plan::Distinct { input } => distinct(self.results(input)),
+            Plan::Limit { input, limit } => limit_by(self.results(input), *limit),
+            Plan::Unit => invalid_plan("Unit"),
+            Plan::Scan { .. } => invalid_plan("Scan"),
+            Plan::SubqueryScan { .. } => invalid_plan("SubqueryScan"),
+            Plan::CrossJoin { .. } => invalid_plan("CrossJoin"),
+            Plan::Filter { .. } => invalid_plan("Filter"),
+            Plan::Aggregate { .. } => invalid_plan("Aggregate"),
+            Plan::Sort { .. } => invalid_plan("Sort"),
+        }
+    }
+
+    fn scan(self, source: &ScanSource) -> Stream<'a, JsonValue> {
+        match source {
+            ScanSource::Name(name) => match self.store.scan(name) {
+                Ok(events) => Box::new(events.map(Ok)),
+                Err(e) => Box::new(std::iter::once(Err(e))),
+            },
+
+            ScanSource::Subject(pattern) => match self.store.scan(EVENTS_SOURCE) {
+                Ok(events) => {
+                    let pattern = pattern.clone();
+
+                    Box::new(
+                        events
+                            .filter(move |event| match event.get("subject") {
+                                Some(JsonValue::String(subject)) => {
+                                    subject_matches(subject, &pattern)
+                                }
+                                _ => false,
+                            })
+                            .map(Ok),
+                    )
+                }
+                Err(e) => Box::new(std::iter::once(Err(e))),
+            },
+        }
+    }
+
+    /// Combines each row of the left input with every row of the right input.
+    ///
+    /// The right input is executed again for each row of the left one instead
+    /// of being kept in memory.
+    fn cross_join(self, lhs: Stream<'a, Row>, rhs: &Plan) -> Stream<'a, Row> {
+        let rhs = rhs.clone();
+
+        Box::new(lhs.flat_map(move |row| -> Stream<'a, Row> {
+            let row = match row {
+                Ok(row) => row,
+                Err(e) => return Box::new(std::iter::once(Err(e))),
+            };
+
+            Box::new(self.rows(&rhs).map(move |other| {
+                let mut env = row.env.clone();
+                env.extend(other?.env);
+
+                Ok(Row { env, group: None })
+            }))
+        }))
+    }
+
+    fn filter(self, rows: Stream<'a, Row>, predicate: &Expr) -> Stream<'a, Row> {
+        let predicate = predicate.clone();
+
+        Box::new(rows.filter_map(move |row| {
+            let row = match row {
+                Ok(row) => row,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match self.eval_predicate(&predicate, row.ctx()) {
+                Ok(true) => Some(Ok(row)),
+                Ok(false) => None,
+                Err(e) => Some(Err(e)),
+            }
+        }))
+    }
+
+    /// Groups rows by key.
+    ///
+    /// Each group is represented by the bindings of its first row, extended
+    /// with the name bound to the group key.
+    fn group(self, rows: Stream<'a, Row>, key: &Expr, binding: &Option<String>) -> Stream<'a, Row> {
+        let key = key.clone();
+        let binding = binding.clone();
+
+        materialize(move || {
+            let mut groups: Vec<(JsonValue, Vec<Env>)> = Vec::new();
+            let mut indexes: HashMap<String, usize> = HashMap::new();
+
+            for row in rows {
+                let row = row?;
+                let key = self.eval_expr(&key, row.ctx())?;
+
+                match indexes.get(&identity(&key)) {
+                    Some(&idx) => groups[idx].1.push(row.env),
+                    None => {
+                        indexes.insert(identity(&key), groups.len());
+                        groups.push((key, vec![row.env]));
+                    }
+                }
+            }
+
+            Ok(groups
+                .into_iter()
+                .map(|(key, group)| {
+                    let mut env = group[0].clone();
+
+                    if let Some(binding) = &binding {
+                        env.insert(binding.clone(), key);
+                    }
+
+                    Row {
+                        env,
+                        group: Some(group),
+                    }
+                })
+                .collect())
+        })
+    }
+
+    fn sort(self, rows: Stream<'a, Row>, expr: &Expr, order: Order) -> Stream<'a, Row> {
+        let expr = expr.clone();
+
+        materialize(move || {
+            let mut keyed = Vec::new();
+
+            for row in rows {
+                let row = row?;
+                keyed.push((self.eval_expr(&expr, row.ctx())?, row));
+            }
+
+            keyed.sort_by(|(a, _), (b, _)| match order {
+                Order::Asc => compare(a, b),
+                Order::Desc => compare(b, a),
+            });
+
+            Ok(keyed.into_iter().map(|(_, row)| row).collect())
+        })
+    }
+
+    fn project(self, rows: Stream<'a, Row>, projection: &Expr) -> Stream<'a, JsonValue> {
+        let projection = projection.clone();
+
+        Box::new(rows.map(move |row| self.eval_expr(&projection, row?.ctx())))
+    }
+
+    fn eval_predicate(&self, expr: &Expr, ctx: Context) -> EvalResult<bool> {
+        match self.eval_expr(expr, ctx)? {
+            JsonValue::Bool(value) => Ok(value),
+            JsonValue::Null => Ok(false),
+            value => Err(type_mismatch(expr.attrs.pos, "bool", &value)),
+        }
+    }
+
+    fn eval_expr(&self, expr: &Expr, ctx: Context) -> EvalResult<JsonValue> {
+        match &expr.value {
+            Value::Number(n) => Ok(number(*n)),
+            Value::String(s) => Ok(JsonValue::String(s.clone())),
+            Value::Bool(b) => Ok(JsonValue::Bool(*b)),
+
+            Value::Id(name) => ctx.env.get(name).cloned().ok_or_else(|| {
+                EvalError::UnknownVariable(expr.attrs.pos.line, expr.attrs.pos.col, name.clone())
+            }),
+
+            Value::Array(elems) => elems
+                .iter()
+                .map(|elem| self.eval_expr(elem, ctx))
+                .collect::<EvalResult<Vec<_>>>()
+                .map(JsonValue::Array),
+
+            Value::Record(fields) => {
+                let mut record = Map::new();
+
+                for field in fields {
+                    record.insert(field.name.clone(), self.eval_expr(&field.value, ctx)?);
+                }
+
+                Ok(JsonValue::Object(record))
+            }
+
+            Value::Access(access) => match self.eval_expr(&access.target, ctx)? {
+                JsonValue::Object(mut record) => {
+                    Ok(record.remove(&access.field).unwrap_or(JsonValue::Null))
+                }
+                JsonValue::Null => Ok(JsonValue::Null),
+                value => Err(type_mismatch(expr.attrs.pos, "record", &value)),
+            },
+
+            Value::App(app) => {
+                if self.functions.is_aggregate(&app.func) {
+                    let Some(group) = ctx.group else {
+                        return Err(EvalError::UnexpectedAggregate(
+                            expr.attrs.pos.line,
+                            expr.attrs.pos.col,
+                            app.func.clone(),
+                        ));
+                    };
+
+                    let mut values = Vec::with_capacity(group.len());
+
+                    for env in group {
+                        let mut args = Vec::with_capacity(app.args.len());
+
+                        for arg in &app.args {
+                            args.push(self.eval_expr(arg, Context::row(env))?);
+                        }
+
+                        values.push(args);
+                    }
+
+                    return aggregate(expr.attrs.pos, &app.func, values);
+                }
+
+                let mut args = Vec::with_capacity(app.args.len());
+
+                for arg in &app.args {
+                    args.push(self.eval_expr(arg, ctx)?);
+                }
+
+                scalar(expr.attrs.pos, &app.func, args)
+            }
+
+            Value::Binary(binary) => match binary.operator {
+                Operator::And => Ok(JsonValue::Bool(
+                    self.eval_predicate(&binary.lhs, ctx)?
+                        && self.eval_predicate(&binary.rhs, ctx)?,
+                )),
+
+                Operator::Or => Ok(JsonValue::Bool(
+                    self.eval_predicate(&binary.lhs, ctx)?
+                        || self.eval_predicate(&binary.rhs, ctx)?,
+                )),
+
+                Operator::Xor => Ok(JsonValue::Bool(
+                    self.eval_predicate(&binary.lhs, ctx)?
+                        != self.eval_predicate(&binary.rhs, ctx)?,
+                )),
+
+                operator => {
+                    let lhs = self.eval_expr(&binary.lhs, ctx)?;
+                    let rhs = self.eval_expr(&binary.rhs, ctx)?;
+
+                    binary_op(expr.attrs.pos, operator, &lhs, &rhs)
+                }
+            },
+
+            Value::Unary(unary) => match (unary.operator, self.eval_expr(&unary.expr, ctx)?) {
+                (_, JsonValue::Null) => Ok(JsonValue::Null),
+                (Operator::Not, JsonValue::Bool(b)) => Ok(JsonValue::Bool(!b)),
+                (Operator::Not, value) => Err(type_mismatch(unary.expr.attrs.pos, "bool", &value)),
+                (Operator::Sub, JsonValue::Number(n)) => Ok(number(-as_f64(&n))),
+                (_, JsonValue::Number(n)) => Ok(JsonValue::Number(n)),
+                (_, value) => Err(type_mismatch(unary.expr.attrs.pos, "number", &value)),
+            },
+
+            Value::Group(inner) => self.eval_expr(inner, ctx),
+        }
+    }
+}
+
+impl<'a> Context<'a> {
+    fn row(env: &'a Env) -> Self {
+        Self { env, group: None }
+    }
+}
+
+/// Binds each value of a stream to a variable.
+fn bind<'a>(binding: &str, values: Stream<'a, JsonValue>) -> Stream<'a, Row> {
+    let binding = binding.to_owned();
+
+    Box::new(values.map(move |value| {
+        Ok(Row {
+            env: Env::from([(binding.clone(), value?)]),
+            group: None,
+        })
+    }))
+}
+
+/// Puts all the rows in a single group, represented by a row binding no
+/// variable.
+fn group_all<'a>(rows: Stream<'a, Row>) -> Stream<'a, Row> {
+    materialize(move || {
+        let group = rows
+            .map(|row| row.map(|row| row.env))
+            .collect::<EvalResult<_>>()?;
+
+        Ok(vec![Row {
+            env: Env::new(),
+            group: Some(group),
+        }])
+    })
+}
+
+fn limit_by<'a, A: 'a>(stream: Stream<'a, A>, limit: Limit) -> Stream<'a, A> {
+    match limit {
+        Limit::Top(n) => Box::new(stream.take(n as usize)),
+        Limit::Skip(n) => Box::new(stream.skip(n as usize)),
+    }
+}
+
+fn invalid_plan<'a, A: 'a>(operator: &'static str) -> Stream<'a, A> {
+    Box::new(std::iter::once(Err(EvalError::InvalidPlan(operator))))
+}
+
+/// Builds an operator that needs all its input before producing any row.
+///
+/// The input is only consumed when the first row is pulled.
+fn materialize<'a, A: 'a>(rows: impl FnOnce() -> EvalResult<Vec<A>> + 'a) -> Stream<'a, A> {
+    Box::new(
+        std::iter::once_with(rows).flat_map(|rows| -> Stream<'a, A> {
+            match rows {
+                Ok(rows) => Box::new(rows.into_iter().map(Ok)),
+                Err(e) => Box::new(std::iter::once(Err(e))),
+            }
+        }),
+    )
+}
+
+/// Discards the results that were already produced.
+fn distinct<'a>(results: Stream<'a, JsonValue>) -> Stream<'a, JsonValue> {
+    let mut seen = HashSet::new();
+
+    Box::new(results.filter(move |result| match result {
+        Ok(value) => seen.insert(identity(value)),
+        Err(_) => true,
+    }))
+}
+
+/// Returns a string identifying a value among the values equal to it, numbers
+/// such as `1` and `1.0` being written the same way.
+fn identity(value: &JsonValue) -> String {
+    normalize(value).to_string()
+}
+
+fn normalize(value: &JsonValue) -> JsonValue {
+    match value {
+        JsonValue::Number(n) => number(as_f64(n)),
+        JsonValue::Array(values) => JsonValue::Array(values.iter().map(normalize).collect()),
+        JsonValue::Object(fields) => JsonValue::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), normalize(value)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+fn binary_op(
+    pos: Pos,
+    operator: Operator,
+    lhs: &JsonValue,
+    rhs: &JsonValue,
+) -> EvalResult<JsonValue> {
+    match operator {
+        Operator::Eq => Ok(JsonValue::Bool(compare(lhs, rhs) == Ordering::Equal)),
+        Operator::Neq => Ok(JsonValue::Bool(compare(lhs, rhs) != Ordering::Equal)),
+        Operator::Lt | Operator::Lte | Operator::Gt | Operator::Gte => {
+            ordered(pos, comparison(operator), lhs, rhs)
+        }
+        _ => arithmetic(pos, arithmetic_op(operator), lhs, rhs),
+    }
+}
+
+/// Returns the test a comparison operator applies to the ordering of its operands.
+pub(crate) fn comparison(operator: Operator) -> fn(Ordering) -> bool {
+    match operator {
+        Operator::Lt => Ordering::is_lt,
+        Operator::Lte => Ordering::is_le,
+        Operator::Gt => Ordering::is_gt,
+        Operator::Gte => Ordering::is_ge,
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the function computing an arithmetic operator, or `None` when
+/// dividing by zero. The remainder of `%` has the sign of the dividend.
+pub(crate) fn arithmetic_op(operator: Operator) -> fn(f64, f64) -> Option<f64> {
+    match operator {
+        Operator::Add => |a, b| Some(a + b),
+        Operator::Sub => |a, b| Some(a - b),
+        Operator::Mul => |a, b| Some(a * b),
+        Operator::Div => |a, b| (b != 0.0).then(|| a / b),
+        Operator::Mod => |a, b| (b != 0.0).then(|| a % b),
+        Operator::Pow => |a, b| Some(a.powf(b)),
+        _ => unreachable!(),
+    }
+}
+
+/// Compares two numbers or two strings.
+pub(crate) fn ordered(
+    pos: Pos,
+    test: fn(Ordering) -> bool,
+    lhs: &JsonValue,
+    rhs: &JsonValue,
+) -> EvalResult<JsonValue> {
+    match (lhs, rhs) {
+        (JsonValue::Null, _) | (_, JsonValue::Null) => Ok(JsonValue::Null),
+        (JsonValue::Number(_), JsonValue::Number(_))
+        | (JsonValue::String(_), JsonValue::String(_)) => {
+            Ok(JsonValue::Bool(test(compare(lhs, rhs))))
+        }
+        (JsonValue::Number(_) | JsonValue::String(_), _) => Err(type_mismatch(pos, kind(lhs), rhs)),
+        _ => Err(type_mismatch(pos, "number or string", lhs)),
+    }
+}
+
+/// Applies an arithmetic operation to two numbers.
+pub(crate) fn arithmetic(
+    pos: Pos,
+    op: fn(f64, f64) -> Option<f64>,
+    lhs: &JsonValue,
+    rhs: &JsonValue,
+) -> EvalResult<JsonValue> {
+    if lhs.is_null() || rhs.is_null() {
+        return Ok(JsonValue::Null);
+    }
+
+    let a = expect_number(pos, lhs)?;
+    let b = expect_number(pos, rhs)?;
+
+    op(a, b)
+        .map(number)
+        .ok_or(EvalError::DivisionByZero(pos.line, pos.col))
+}
+
+pub(crate) fn scalar(pos: Pos, func: &str, args: Vec<JsonValue>) -> EvalResult<JsonValue> {
+    let name = func.to_ascii_uppercase();
+    let expect_arity = |arity: usize| {
+        if args.len() == arity {
+            Ok(())
+        } else {
+            Err(EvalError::WrongArity(
+                pos.line,
+                pos.col,
+                func.to_owned(),
+                arity,
+                args.len(),
+            ))
+        }
+    };
+
+    match name.as_str() {
+        "PI" => expect_arity(0).map(|_| number(std::f64::consts::PI)),
+        "RAND" => expect_arity(0).map(|_| number(fastrand::f64())),
+        "NOW" => expect_arity(0).map(|_| JsonValue::String(now())),
+        _ => {
+            let arity = match name.as_str() {
+                "POW" | "INSTR" | "STARTSWITH" | "ENDSWITH" => 2,
+                "SUBSTRING" | "REPLACE" => 3,
+                _ => 1,
+            };
+
+            expect_arity(arity)?;
+
+            if args.iter().any(JsonValue::is_null) {
+                return Ok(JsonValue::Null);
+            }
+
+            match name.as_str() {
+                "ABS" | "CEIL" | "FLOOR" | "ROUND" | "COS" | "SIN" | "TAN" | "EXP" | "SQRT" => {
+                    let n = expect_number(pos, &args[0])?;
+
+                    Ok(number(match name.as_str() {
+                        "ABS" => n.abs(),
+                        "CEIL" => n.ceil(),
+                        "FLOOR" => n.floor(),
+                        "ROUND" => n.round(),
+                        "COS" => n.cos(),
+                        "SIN" => n.sin(),
+                        "TAN" => n.tan(),
+                        "EXP" => n.exp(),
+                        _ => n.sqrt(),
+                    }))
+                }
+
+                "POW" => Ok(number(
+                    expect_number(pos, &args[0])?.powf(expect_number(pos, &args[1])?),
+                )),
+
+                "LOWER" | "UPPER" | "TRIM" | "LTRIM" | "RTRIM" => {
+                    let s = expect_string(pos, &args[0])?;
+
+                    Ok(JsonValue::String(match name.as_str() {
+                        "LOWER" => s.to_lowercase(),
+                        "UPPER" => s.to_uppercase(),
+                        "TRIM" => s.trim().to_owned(),
+                        "LTRIM" => s.trim_start().to_owned(),
+                        _ => s.trim_end().to_owned(),
+                    }))
+                }
+
+                "LEN" => Ok(number(expect_string(pos, &args[0])?.chars().count() as f64)),
+
+                "INSTR" => {
+                    let s = expect_string(pos, &args[0])?;
+                    let needle = expect_string(pos, &args[1])?;
+
+                    Ok(number(match s.find(needle) {
+                        Some(idx) => (s[..idx].chars().count() + 1) as f64,
+                        None => 0.0,
+                    }))
+                }
+
+                "SUBSTRING" => {
+                    let s = expect_string(pos, &args[0])?;
+                    let start = expect_number(pos, &args[1])?.max(1.0) as usize;
+                    let len = expect_number(pos, &args[2])?.max(0.0) as usize;
+
+                    Ok(JsonValue::String(
+                        s.chars().skip(start - 1).take(len).collect(),
+                    ))
+                }
+
+                "REPLACE" => Ok(JsonValue::String(
+                    expect_string(pos, &args[0])?
+                        .replace(expect_string(pos, &args[1])?, expect_string(pos, &args[2])?),
+                )),
+
+                "STARTSWITH" => Ok(JsonValue::Bool(
+                    expect_string(pos, &args[0])?.starts_with(expect_string(pos, &args[1])?),
+                )),
+
+                "ENDSWITH" => Ok(JsonValue::Bool(
+                    expect_string(pos, &args[0])?.ends_with(expect_string(pos, &args[1])?),
+                )),
+
+                "YEAR" | "MONTH" | "DAY" | "HOUR" | "MINUTE" | "SECOND" | "WEEKDAY" => {
+                    let s = expect_string(pos, &args[0])?;
+                    let Some([year, month, day, hour, minute, second]) = date_time(s) else {
+                        return Ok(JsonValue::Null);
+                    };
+
+                    Ok(number(match name.as_str() {
+                        "YEAR" => year,
+                        "MONTH" => month,
+                        "DAY" => day,
+                        "HOUR" => hour,
+                        "MINUTE" => minute,
+                        "SECOND" => second,
+                        _ => (days_from_civil(year, month, day) + 4).rem_euclid(7),
+                    } as f64))
+                }
+
+                _ => Err(EvalError::UnknownFunction(
+                    pos.line,
+                    pos.col,
+                    func.to_owned(),
+                )),
+            }
+        }
+    }
+}
+
+/// Computes an aggregate function from the arguments it received for each row of a group.
+fn aggregate(pos: Pos, func: &str, rows: Vec<Vec<JsonValue>>) -> EvalResult<JsonValue> {
+    let name = func.to_ascii_uppercase();
+
+    if name == "COUNT" {
+        return Ok(number(rows.len() as f64));
+    }
+
+    let mut values = Vec::with_capacity(rows.len());
+
+    for args in rows {
+        if args.len() != 1 {
+            return Err(EvalError::WrongArity(
+                pos.line,
+                pos.col,
+                func.to_owned(),
+                1,
+                args.len(),
+            ));
+        }
+
+        if !args[0].is_null() {
+            values.push(expect_number(pos, &args[0])?);
+        }
+    }
+
+    if name == "SUM" {
+        return Ok(number(values.iter().sum()));
+    }
+
+    if values.is_empty() {
+        return Ok(JsonValue::Null);
+    }
+
+    let len = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / len;
+    let variance = values.iter().map(|n| (n - mean).powi(2)).sum::<f64>() / len;
+
+    Ok(number(match name.as_str() {
+        "AVG" => mean,
+        "MIN" => values.iter().copied().fold(f64::INFINITY, f64::min),
+        "MAX" => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        "MEDIAN" => {
+            values.sort_by(f64::total_cmp);
+            let mid = values.len() / 2;
+
+            if values.len() % 2 == 0 {
+                (values[mid - 1] + values[mid]) / 2.0
+            } else {
+                values[mid]
+            }
+        }
+        "VARIANCE" => variance,
+        "STDDEV" => variance.sqrt(),
+        _ => {
+            return Err(EvalError::UnknownFunction(
+                pos.line,
+                pos.col,
+                func.to_owned(),
+            ));
+        }
+    }))
+}
+
+/// Total order over JSON values used by comparisons, equality and ORDER BY.
+///
+/// Values of different kinds are ordered as follows: null, booleans, numbers,
+/// strings, arrays and records.
+pub(crate) fn compare(a: &JsonValue, b: &JsonValue) -> Ordering {
+    match (a, b) {
+        (JsonValue::Bool(a), JsonValue::Bool(b)) => a.cmp(b),
+        (JsonValue::Number(a), JsonValue::Number(b)) => as_f64(a).total_cmp(&as_f64(b)),
+        (JsonValue::String(a), JsonValue::String(b)) => a.cmp(b),
+        (JsonValue::Array(a), JsonValue::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (JsonValue::Object(a), JsonValue::Object(b)) => a
+            .iter()
+            .zip(b)
+            .map(|((ka, va), (kb, vb))| ka.cmp(kb).then_with(|| compare(va, vb)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn rank(value: &JsonValue) -> u8 {
+    match value {
+        JsonValue::Null => 0,
+        JsonValue::Bool(_) => 1,
+        JsonValue::Number(_) => 2,
+        JsonValue::String(_) => 3,
+        JsonValue::Array(_) => 4,
+        JsonValue::Object(_) => 5,
+    }
+}
+
+fn kind(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "bool",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "record",
+    }
+}
+
+pub(crate) fn type_mismatch(pos: Pos, expected: &'static str, found: &JsonValue) -> EvalError {
+    EvalError::TypeMismatch(pos.line, pos.col, expected, kind(found))
+}
+
+pub(crate) fn expect_number(pos: Pos, value: &JsonValue) -> EvalResult<f64> {
+    match value {
+        JsonValue::Number(n) => Ok(as_f64(n)),
+        value => Err(type_mismatch(pos, "number", value)),
+    }
+}
+
+fn expect_string(pos: Pos, value: &JsonValue) -> EvalResult<&str> {
+    match value {
+        JsonValue::String(s) => Ok(s),
+        value => Err(type_mismatch(pos, "string", value)),
+    }
+}
+
+pub(crate) fn as_f64(n: &Number) -> f64 {
+    n.as_f64().unwrap_or_default()
+}
+
+/// Converts a number to JSON, preferring integers when there is no fractional
+/// part. Numbers JSON cannot represent (`NaN`, infinities) become null.
+pub(crate) fn number(n: f64) -> JsonValue {
+    if n.fract() == 0.0 && n.abs() < i64::MAX as f64 {
+        return JsonValue::Number(Number::from(n as i64));
+    }
+
+    Number::from_f64(n).map_or(JsonValue::Null, JsonValue::Number)
+}
+
+/// Returns true if `subject` is `pattern` or one of its descendants.
+fn subject_matches(subject: &str, pattern: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+
+    match subject.strip_prefix(pattern) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/') || pattern.is_empty(),
+        None => false,
+    }
+}
+
+/// Extracts year, month, day, hour, minute and second from an RFC 3339 timestamp.
+fn date_time(s: &str) -> Option<[i64; 6]> {
+    let field = |range: std::ops::Range<usize>| s.get(range)?.parse::<i64>().ok();
+
+    Some([
+        field(0..4)?,
+        field(5..7)?,
+        field(8..10)?,
+        field(11..13).unwrap_or(0),
+        field(14..16).unwrap_or(0),
+        field(17..19).unwrap_or(0),
+    ])
+}
+
+/// Number of days between 1970-01-01 and the given date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+/// Current time as an RFC 3339 timestamp in UTC.
+fn now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default();
+
+    let days = secs.div_euclid(86400);
+    let time = secs.rem_euclid(86400);
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Lazy sequence of the results of a query, see [`eval_iter`].
+pub struct Results<'a> {
+    inner: Stream<'a, JsonValue>,
+}
+
+impl Iterator for Results<'_> {
+    type Item = EvalResult<JsonValue>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// Built-in functions, used to tell aggregates apart from scalar functions.
+pub(crate) fn builtins() -> &'static FunctionRegistry {
+    static BUILTINS: OnceLock<FunctionRegistry> = OnceLock::new();
+
+    BUILTINS.get_or_init(FunctionRegistry::default)
+}
+
+/// Evaluate a query against the events of a store, one result at a time.
+///
+/// Events are only read from the store as results are pulled from the
+/// returned iterator: `FROM e IN events WHERE ... TOP 10 PROJECT INTO e` stops
+/// reading events after ten matches. GROUP BY and ORDER BY clauses need to
+/// read all their input before producing a result, and DISTINCT remembers the
+/// results it already produced.
+///
+/// After an error, the iterator should not be polled any further.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::eval_iter;
+/// use serde_json::json;
+///
+/// let events = vec![json!({ "id": "1" }), json!({ "id": "2" }), json!({ "id": "3" })];
+/// let query = parse_query("FROM e IN events TOP 2 PROJECT INTO e.id").unwrap();
+/// let mut results = eval_iter(&query, events.as_slice());
+///
+/// assert_eq!(results.next().unwrap().unwrap(), json!("1"));
+/// assert_eq!(results.next().unwrap().unwrap(), json!("2"));
+/// assert!(results.next().is_none());
+/// ```
+pub fn eval_iter<'a, S>(query: &Query, store: &'a S) -> Results<'a>
+where
+    S: EventStore + ?Sized,
+{
+    eval_plan_iter(&lower_with_functions(&optimize(query), builtins()), store)
+}
+
+/// Execute a logical plan against the events of a store, one result at a time.
+///
+/// This is what [`eval_iter`] does once it optimized and lowered its query,
+/// and lets callers run plans they rewrote. The plan must end with a projection, optionally
+/// followed by distincts and limits, and cannot use projections and distincts
+/// anywhere else but at the root of subqueries.
+///
+/// # Errors
+///
+/// - [`EvalError::InvalidPlan`] when an operator is used where it cannot be executed
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::{eval_plan_iter, lower};
+/// use serde_json::json;
+///
+/// let events = vec![json!({ "id": "1" }), json!({ "id": "2" })];
+/// let query = parse_query("FROM e IN events PROJECT INTO e.id").unwrap();
+/// let results = eval_plan_iter(&lower(&query), events.as_slice());
+///
+/// assert_eq!(results.collect::<Result<Vec<_>, _>>().unwrap(), vec![json!("1"), json!("2")]);
+/// ```
+pub fn eval_plan_iter<'a, S>(plan: &Plan, store: &'a S) -> Results<'a>
+where
+    S: EventStore + ?Sized,
+{
+    let evaluator = Evaluator {
+        store,
+        functions: builtins(),
+    };
+
+    Results {
+        inner: evaluator.results(plan),
+    }
+}
+
+/// Evaluate a query against the events of a store.
+///
+/// The query is expected to have gone through [`analyze`](crate::prelude::analyze)
+/// first: the interpreter does not check names or types ahead of time and
+/// reports such issues only when it runs into them.
+///
+/// Missing fields evaluate to `null`. Arithmetic and comparisons involving
+/// `null` yield `null`, except for equality, and a `null` predicate is treated
+/// as `false`.
+///
+/// This collects all the results of [`eval_iter`].
+///
+/// # Errors
+///
+/// - [`EvalError::UnknownSource`] when the store does not provide a source
+/// - [`EvalError::TypeMismatch`] when a value does not have the expected type
+/// - [`EvalError::DivisionByZero`] when dividing by zero
+/// - [`EvalError::UnknownFunction`] when calling a function that is not built
+///   in, such as a function registered for the analysis
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::eval;
+/// use serde_json::json;
+///
+/// let events = vec![
+///     json!({ "id": "1", "type": "book-acquired", "data": { "price": 10 } }),
+///     json!({ "id": "2", "type": "book-acquired", "data": { "price": 42 } }),
+/// ];
+///
+/// let query = parse_query(
+///     "FROM e IN events WHERE e.data.price > 20 PROJECT INTO { id: e.id }"
+/// ).unwrap();
+///
+/// assert_eq!(eval(&query, events.as_slice()).unwrap(), vec![json!({ "id": "2" })]);
+/// ```
+pub fn eval<S>(query: &Query, store: &S) -> EvalResult<Vec<JsonValue>>
+where
+    S: EventStore + ?Sized,
+{
+    eval_iter(query, store).collect()
+}
@@ -0,0 +1,174 @@
+//! EXPLAIN output for EventQL.
+//!
+//! This module describes how a query runs: the operators of its logical
+//! [`Plan`](crate::prelude::Plan), the query, or subquery, each of them belongs
+//! to, and the filters that were pushed down from an enclosing query. The
+//! result can be printed as a tree for humans or serialized, for instance to
+//! JSON, for tools.
+//!
+//! # Main Function
+//!
+//! - [`explain`] - Describe how a Query runs
+//! - [`explain_optimized`] - Describe how a Query runs, with its subqueries flattened
+use crate::ast::{Expr, Query, Value};
+use crate::optimize::{optimize, push_down_predicates, simplify_clauses};
+use crate::plan::{Plan, lower};
+use crate::token::Operator;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// An operator of an explained query, see [`explain`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ExplainNode {
+    /// Name of the operator (e.g., `Filter`)
+    pub operator: &'static str,
+    /// Arguments of the operator in EventQL syntax (e.g., `e.price > 100`),
+    /// empty if it has none
+    pub detail: String,
+    /// Scope of the query the operator belongs to, see [`Attrs::scope`](crate::Attrs::scope)
+    pub scope: u64,
+    /// For a filter with conditions written in an enclosing query and pushed
+    /// down into this one, scope of that enclosing query
+    pub pushed_down_from: Option<u64>,
+    /// Operators producing the input of this one
+    pub inputs: Vec<ExplainNode>,
+}
+
+impl ExplainNode {
+    fn new(plan: &Plan, scope: u64) -> Self {
+        let pushed_down_from = match plan {
+            Plan::Filter { predicate, .. } => foreign_scope(predicate, scope),
+            _ => None,
+        };
+
+        let input_scope = match plan {
+            Plan::SubqueryScan { scope, .. } => *scope,
+            _ => scope,
+        };
+
+        Self {
+            operator: plan.name(),
+            detail: plan.detail(),
+            scope,
+            pushed_down_from,
+            inputs: plan
+                .inputs()
+                .into_iter()
+                .map(|input| Self::new(input, input_scope))
+                .collect(),
+        }
+    }
+
+    fn fmt_node(
+        &self,
+        f: &mut Formatter<'_>,
+        depth: usize,
+        parent: Option<u64>,
+    ) -> std::fmt::Result {
+        write!(f, "{:indent$}{}", "", self.operator, indent = depth * 2)?;
+
+        if !self.detail.is_empty() {
+            write!(f, " {}", self.detail)?;
+        }
+
+        if parent != Some(self.scope) {
+            write!(f, " (scope {})", self.scope)?;
+        }
+
+        if let Some(scope) = self.pushed_down_from {
+            write!(f, " (pushed down from scope {})", scope)?;
+        }
+
+        writeln!(f)?;
+
+        for input in &self.inputs {
+            input.fmt_node(f, depth + 1, Some(self.scope))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the scope of the first condition of a predicate that was not
+/// written in the given scope, looking through its top-level ANDs.
+fn foreign_scope(predicate: &Expr, scope: u64) -> Option<u64> {
+    match &predicate.value {
+        Value::Binary(binary) if binary.operator == Operator::And => {
+            foreign_scope(&binary.lhs, scope).or_else(|| foreign_scope(&binary.rhs, scope))
+        }
+        Value::Group(inner) => foreign_scope(inner, scope),
+        _ if predicate.attrs.scope != scope => Some(predicate.attrs.scope),
+        _ => None,
+    }
+}
+
+impl Display for ExplainNode {
+    /// Writes the operators as a tree, inputs being indented beneath the
+    /// operator consuming them. The scope is written on the first operator of
+    /// each query.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_node(f, 0, None)
+    }
+}
+
+/// Describe how a query runs, once its predicates are pushed down and
+/// simplified.
+///
+/// Subqueries are kept, so that the plan follows the structure of the query
+/// text. See [`explain_optimized`] for the plan of a fully [`optimize`]d query.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::explain;
+///
+/// let query = parse_query(
+///     "FROM o IN (FROM e IN events ORDER BY e.time DESC PROJECT INTO { id: e.id }) \
+///      WHERE o.id != \"0\" \
+///      TOP 1 \
+///      PROJECT INTO o.id"
+/// ).unwrap();
+///
+/// assert_eq!(
+///     explain(&query).to_string(),
+///     "Limit TOP 1 (scope 1)\n  \
+///        Project o.id\n    \
+///          SubqueryScan o\n      \
+///            Project {id: e.id} (scope 2)\n        \
+///              Sort e.time DESC\n          \
+///                Filter e.id != \"0\" (pushed down from scope 1)\n            \
+///                  Scan e IN events\n",
+/// );
+/// ```
+pub fn explain(query: &Query) -> ExplainNode {
+    let query = simplify_clauses(&push_down_predicates(query));
+
+    ExplainNode::new(&lower(&query), query.attrs.scope)
+}
+
+/// Describe how a query runs, once [`optimize`]d.
+///
+/// Unlike [`explain`], subqueries that can be flattened are inlined into the
+/// queries reading them.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::explain_optimized;
+///
+/// let query = parse_query(
+///     "FROM o IN (FROM e IN events PROJECT INTO { id: e.id }) TOP 1 PROJECT INTO o.id"
+/// ).unwrap();
+///
+/// assert_eq!(
+///     explain_optimized(&query).to_string(),
+///     "Limit TOP 1 (scope 1)\n  \
+///        Project e.id\n    \
+///          Scan e IN events\n",
+/// );
+/// ```
+pub fn explain_optimized(query: &Query) -> ExplainNode {
+    ExplainNode::new(&lower(&optimize(query)), query.attrs.scope)
+}
@@ -0,0 +1,210 @@
+//! Function catalog for EventQL.
+//!
+//! This module describes the functions that can be called from a query, such
+//! as `UPPER(e.data.name)` or `COUNT()`. Each function has a signature giving
+//! the types of its arguments, the type of its result, and whether it is a
+//! scalar function or an aggregate over a group of rows.
+//!
+//! # Core Types
+//!
+//! - [`FunctionRegistry`] - Catalog of the functions known to semantic analysis
+//! - [`FunctionSig`] - Signature of a function
+//! - [`FunctionKind`] - Scalar or aggregate function
+use crate::ast::{Expr, Type, Value};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Whether a function computes a value per row or per group of rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum FunctionKind {
+    /// Function computing a value from the values of a single row
+    Scalar,
+    /// Function computing a value from all the rows of a group
+    Aggregate,
+}
+
+/// Signature of a function.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::Type;
+/// use eventql_parser::prelude::{FunctionKind, FunctionSig};
+///
+/// let sig = FunctionSig::scalar(vec![Type::String], Type::Number);
+/// assert_eq!(sig.kind, FunctionKind::Scalar);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FunctionSig {
+    /// Types of the arguments of the function
+    pub args: Vec<Type>,
+    /// Type of the value returned by the function
+    pub result: Type,
+    /// Whether the function is a scalar function or an aggregate
+    pub kind: FunctionKind,
+}
+
+impl FunctionSig {
+    /// Create the signature of a scalar function.
+    pub fn scalar(args: Vec<Type>, result: Type) -> Self {
+        Self {
+            args,
+            result,
+            kind: FunctionKind::Scalar,
+        }
+    }
+
+    /// Create the signature of an aggregate function.
+    pub fn aggregate(args: Vec<Type>, result: Type) -> Self {
+        Self {
+            args,
+            result,
+            kind: FunctionKind::Aggregate,
+        }
+    }
+}
+
+/// Catalog of the functions that can be called from a query.
+///
+/// Function names are case-insensitive. The default registry contains the
+/// built-in functions of EventQL; embedders can register their own functions
+/// on top of them.
+///
+/// A registry only describes signatures: registered functions are known to
+/// semantic analysis, but the interpreter of the `eval` feature only
+/// implements the built-in functions and reports any other function as
+/// unknown. Queries calling custom functions are meant to be run by the
+/// embedder's own engine.
+///
+/// # Built-in Functions
+///
+/// - **Math**: `ABS`, `CEIL`, `FLOOR`, `ROUND`, `COS`, `SIN`, `TAN`, `EXP`, `SQRT`, `POW`, `RAND`, `PI`
+/// - **String**: `LOWER`, `UPPER`, `TRIM`, `LTRIM`, `RTRIM`, `LEN`, `INSTR`, `SUBSTRING`, `REPLACE`,
+///   `STARTSWITH`, `ENDSWITH`
+/// - **Date**: `NOW`, `YEAR`, `MONTH`, `DAY`, `HOUR`, `MINUTE`, `SECOND`, `WEEKDAY`
+/// - **Aggregate**: `COUNT`, `SUM`, `AVG`, `MEDIAN`, `MIN`, `MAX`, `STDDEV`, `VARIANCE`
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::Type;
+/// use eventql_parser::prelude::{FunctionRegistry, FunctionSig};
+///
+/// let mut registry = FunctionRegistry::default();
+/// registry.register("SLUGIFY", FunctionSig::scalar(vec![Type::String], Type::String));
+///
+/// assert!(registry.lookup("count").is_some());
+/// assert!(registry.lookup("slugify").is_some());
+/// assert!(registry.lookup("unknown").is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, FunctionSig>,
+}
+
+impl FunctionRegistry {
+    /// Create a registry without any function.
+    pub fn empty() -> Self {
+        Self {
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Register a function, replacing any function previously registered under the same name.
+    ///
+    /// Only the signature is registered, see the [type documentation](Self)
+    /// about evaluating custom functions.
+    pub fn register(&mut self, name: impl AsRef<str>, sig: FunctionSig) -> &mut Self {
+        self.functions
+            .insert(name.as_ref().to_ascii_uppercase(), sig);
+        self
+    }
+
+    /// Look a function up by name, ignoring case.
+    pub fn lookup(&self, name: &str) -> Option<&FunctionSig> {
+        self.functions.get(&name.to_ascii_uppercase())
+    }
+
+    /// Tell whether a function is a registered aggregate, ignoring case.
+    pub fn is_aggregate(&self, name: &str) -> bool {
+        self.lookup(name)
+            .is_some_and(|sig| sig.kind == FunctionKind::Aggregate)
+    }
+
+    /// Tell whether an expression calls a registered aggregate, possibly nested
+    /// within other expressions.
+    pub(crate) fn has_aggregate(&self, expr: &Expr) -> bool {
+        if let Value::App(app) = &expr.value
+            && self.is_aggregate(&app.func)
+        {
+            return true;
+        }
+
+        expr.children().any(|child| self.has_aggregate(child))
+    }
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        for name in [
+            "ABS", "CEIL", "FLOOR", "ROUND", "COS", "SIN", "TAN", "EXP", "SQRT",
+        ] {
+            registry.register(name, FunctionSig::scalar(vec![Type::Number], Type::Number));
+        }
+
+        registry.register(
+            "POW",
+            FunctionSig::scalar(vec![Type::Number, Type::Number], Type::Number),
+        );
+
+        for name in ["RAND", "PI"] {
+            registry.register(name, FunctionSig::scalar(vec![], Type::Number));
+        }
+
+        for name in ["LOWER", "UPPER", "TRIM", "LTRIM", "RTRIM"] {
+            registry.register(name, FunctionSig::scalar(vec![Type::String], Type::String));
+        }
+
+        registry.register("LEN", FunctionSig::scalar(vec![Type::String], Type::Number));
+        registry.register(
+            "INSTR",
+            FunctionSig::scalar(vec![Type::String, Type::String], Type::Number),
+        );
+        registry.register(
+            "SUBSTRING",
+            FunctionSig::scalar(vec![Type::String, Type::Number, Type::Number], Type::String),
+        );
+        registry.register(
+            "REPLACE",
+            FunctionSig::scalar(vec![Type::String, Type::String, Type::String], Type::String),
+        );
+
+        for name in ["STARTSWITH", "ENDSWITH"] {
+            registry.register(
+                name,
+                FunctionSig::scalar(vec![Type::String, Type::String], Type::Bool),
+            );
+        }
+
+        registry.register("NOW", FunctionSig::scalar(vec![], Type::String));
+
+        for name in [
+            "YEAR", "MONTH", "DAY", "HOUR", "MINUTE", "SECOND", "WEEKDAY",
+        ] {
+            registry.register(name, FunctionSig::scalar(vec![Type::String], Type::Number));
+        }
+
+        registry.register("COUNT", FunctionSig::aggregate(vec![], Type::Number));
+
+        for name in ["SUM", "AVG", "MEDIAN", "MIN", "MAX", "STDDEV", "VARIANCE"] {
+            registry.register(
+                name,
+                FunctionSig::aggregate(vec![Type::Number], Type::Number),
+            );
+        }
+
+        registry
+    }
+}
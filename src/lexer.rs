@@ -4,19 +4,25 @@
 //! into a sequence of tokens. The tokenizer is built using the `nom` parser
 //! combinator library.
 //!
-//! # Main Function
+//! # Main Functions
 //!
 //! - [`tokenize`] - Convert a query string into a vector of tokens
+//! - [`tokenize_with_comments`] - Same, keeping comments as tokens
+use crate::error::LexerError;
 use crate::token::{Operator, Sym, Symbol, Text, Token};
 use nom::branch::alt;
-use nom::bytes::complete::take_while;
-use nom::character::complete::{alpha1, alphanumeric0, char, multispace0};
-use nom::character::one_of;
+use nom::bytes::complete::{tag, take_while};
+use nom::character::complete::{char, digit1, multispace0, one_of, satisfy};
 use nom::combinator::{eof, opt, recognize};
-use nom::error::{Error, context};
-use nom::number::complete::double;
+use nom::error::{ContextError, ErrorKind, ParseError, context};
 use nom::sequence::{delimited, pair};
-use nom::{IResult, Parser};
+use nom::{IResult, Input, Parser};
+use unicode_xid::UnicodeXID;
+
+/// Result type for lexer operations.
+///
+/// This is a convenience alias for `Result<T, LexerError>`.
+pub type LexResult<A> = Result<A, LexerError>;
 
 /// Tokenize an EventQL query string.
 ///
@@ -25,20 +31,63 @@ use nom::{IResult, Parser};
 /// and column numbers) for error reporting.
 /// # Recognized Tokens
 ///
-/// - **Identifiers**: Alphanumeric names starting with a letter (e.g., `events`, `e`)
+/// - **Identifiers**: Names made of letters, digits and `_`, not starting with a digit
+///   (e.g., `events`, `order_id`, `prix_unité`), or quoted with backticks (e.g., `` `content-type` ``)
 /// - **Keywords**: Case-insensitive SQL-like keywords detected by the parser
-/// - **Numbers**: Floating-point literals (e.g., `42`, `3.14`)
-/// - **Strings**: Double-quoted string literals (e.g., `"hello"`)
-/// - **Operators**: Arithmetic (`+`, `-`, `*`, `/`), comparison (`==`, `!=`, `<`, `<=`, `>`, `>=`), logical (`AND`, `OR`, `XOR`, `NOT`)
+/// - **Numbers**: Unsigned floating-point literals (e.g., `42`, `3.14`, `1e3`), a leading `-` being lexed as an operator
+/// - **Strings**: Double or single-quoted string literals (e.g., `"hello"`, `'hello'`),
+///   with escape sequences (`\"`, `\'`, `\\`, `\n`, `\r`, `\t`, `\u{1F600}`)
+/// - **Operators**: Arithmetic (`+`, `-`, `*`, `/`, `%`, `^`), comparison (`==`, `!=`, `<`, `<=`, `>`, `>=`), logical (`AND`, `OR`, `XOR`, `NOT`)
 /// - **Symbols**: Structural characters (`(`, `)`, `[`, `]`, `{`, `}`, `.`, `,`, `:`)
-pub fn tokenize(input: &str) -> Result<Vec<Token<'_>>, nom::Err<Error<Text<'_>>>> {
+///
+/// Line comments (`-- ...`) and block comments (`/* ... */`) are skipped like
+/// whitespace. A line comment starts with `--` followed by whitespace or the
+/// end of the input, `e.a--4` reading as `e.a - -4`.
+///
+/// # Errors
+///
+/// - [`LexerError::UnterminatedString`] when a string literal is not closed
+/// - [`LexerError::InvalidEscape`] when a string literal contains an unknown escape sequence
+/// - [`LexerError::InvalidNumber`] when a number is malformed (e.g., `1e`, `12abc`)
+/// - [`LexerError::UnterminatedComment`] when a block comment is not closed
+/// - [`LexerError::UnexpectedCharacter`] when a character cannot start any token
+pub fn tokenize(input: &str) -> LexResult<Vec<Token<'_>>> {
+    lex(input, false)
+}
+
+/// Tokenize an EventQL query string, keeping its comments as
+/// [`Sym::Comment`] tokens.
+///
+/// This is meant for tools that need to preserve comments, such as
+/// formatters. [`parse`](crate::prelude::parse) skips these tokens.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::prelude::{Sym, tokenize_with_comments};
+///
+/// let tokens = tokenize_with_comments("-- recent orders\nFROM e IN events PROJECT INTO e").unwrap();
+///
+/// assert!(matches!(tokens[0].sym, Sym::Comment("-- recent orders")));
+/// assert!(matches!(tokens[1].sym, Sym::Id("FROM")));
+/// assert_eq!((tokens[1].line, tokens[1].col), (2, 1));
+/// ```
+pub fn tokenize_with_comments(input: &str) -> LexResult<Vec<Token<'_>>> {
+    lex(input, true)
+}
+
+fn lex(input: &str, keep_comments: bool) -> LexResult<Vec<Token<'_>>> {
     let mut input = Text::new(input);
     let mut tokens = Vec::new();
 
     loop {
-        let (remaining, token) = token(input)?;
+        let (remaining, token) = token(input).map_err(lexer_error)?;
         input = remaining;
 
+        if matches!(token.sym, Sym::Comment(_)) && !keep_comments {
+            continue;
+        }
+
         tokens.push(token);
 
         if matches!(token.sym, Sym::Eof) {
@@ -49,16 +98,122 @@ pub fn tokenize(input: &str) -> Result<Vec<Token<'_>>, nom::Err<Error<Text<'_>>>
     Ok(tokens)
 }
 
-fn token(input: Text) -> IResult<Text, Token> {
+fn token(input: Text) -> LexOutput<Token> {
     delimited(
         multispace0,
-        alt((end_of_file, symbol, operator, ident, number, string)),
+        alt((
+            end_of_file,
+            comment,
+            symbol,
+            operator,
+            ident,
+            quoted_ident,
+            number,
+            string,
+        )),
         multispace0,
     )
     .parse(input)
 }
 
-fn symbol(input: Text) -> IResult<Text, Token> {
+/// Error of the token lexers: where lexing stopped and, for a malformed
+/// token, the error to report.
+struct LexError<'a> {
+    input: Text<'a>,
+    error: Option<LexerError>,
+}
+
+impl<'a> LexError<'a> {
+    /// Fails on a malformed token, reported at the position where the token
+    /// or its faulty part starts.
+    fn failure(input: Text<'a>, error: fn(u32, u32) -> LexerError) -> nom::Err<Self> {
+        let error = error(input.location_line(), input.get_utf8_column() as u32);
+
+        nom::Err::Failure(Self {
+            input,
+            error: Some(error),
+        })
+    }
+}
+
+impl<'a> ParseError<Text<'a>> for LexError<'a> {
+    fn from_error_kind(input: Text<'a>, _: ErrorKind) -> Self {
+        Self { input, error: None }
+    }
+
+    fn append(_: Text<'a>, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'a> ContextError<Text<'a>> for LexError<'a> {}
+
+type LexOutput<'a, A> = IResult<Text<'a>, A, LexError<'a>>;
+
+/// Converts the error of the token lexers, a character that no lexer accepts
+/// being unexpected.
+fn lexer_error(err: nom::Err<LexError>) -> LexerError {
+    let err = match err {
+        nom::Err::Incomplete(_) => return LexerError::IncompleteInput,
+        nom::Err::Error(err) | nom::Err::Failure(err) => err,
+    };
+
+    if let Some(error) = err.error {
+        return error;
+    }
+
+    match err.input.fragment().chars().next() {
+        Some(c) => LexerError::UnexpectedCharacter(
+            err.input.location_line(),
+            err.input.get_utf8_column() as u32,
+            c,
+        ),
+        None => LexerError::IncompleteInput,
+    }
+}
+
+/// Lexes a line comment, running until the end of the line, or a block
+/// comment, which may span several lines.
+fn comment(input: Text) -> LexOutput<Token> {
+    alt((line_comment, block_comment))
+        .map(|value: Text| Token {
+            sym: Sym::Comment(value.fragment()),
+            line: input.location_line(),
+            col: input.get_utf8_column() as u32,
+        })
+        .parse(input)
+}
+
+/// Lexes a line comment, whose `--` must be followed by whitespace or the end
+/// of the input so that `e.a--4` still reads as a subtraction.
+fn line_comment(input: Text) -> LexOutput<Text> {
+    let (rest, _) = tag("--").parse(input)?;
+
+    if !rest
+        .fragment()
+        .chars()
+        .next()
+        .is_none_or(char::is_whitespace)
+    {
+        return Err(nom::Err::Error(LexError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        )));
+    }
+
+    recognize(pair(tag("--"), take_while(|c| c != '\n'))).parse(input)
+}
+
+fn block_comment(input: Text) -> LexOutput<Text> {
+    let (rest, _) = tag("/*").parse(input)?;
+
+    match rest.fragment().find("*/") {
+        Some(idx) => Ok(input.take_split(idx + 4)),
+        None => Err(LexError::failure(input, LexerError::UnterminatedComment)),
+    }
+}
+
+fn symbol(input: Text) -> LexOutput<Token> {
     one_of("().,:[]{}")
         .map(|c| match c {
             '(' => Symbol::OpenParen,
@@ -75,42 +230,44 @@ fn symbol(input: Text) -> IResult<Text, Token> {
         .map(move |sym| Token {
             sym: Sym::Symbol(sym),
             line: input.location_line(),
-            col: input.get_column() as u32,
+            col: input.get_utf8_column() as u32,
         })
         .parse(input)
 }
 
-fn end_of_file(input: Text) -> IResult<Text, Token> {
+fn end_of_file(input: Text) -> LexOutput<Token> {
     eof.map(|_| Token {
         sym: Sym::Eof,
         line: input.location_line(),
-        col: input.get_column() as u32,
+        col: input.get_utf8_column() as u32,
     })
     .parse(input)
 }
 
-fn operator(input: Text) -> IResult<Text, Token> {
+fn operator(input: Text) -> LexOutput<Token> {
     alt((operator_1, operator_2)).parse(input)
 }
 
-fn operator_1(input: Text) -> IResult<Text, Token> {
-    one_of("+-*/^")
+fn operator_1(input: Text) -> LexOutput<Token> {
+    one_of("+-*/%^")
         .map(|c| match c {
             '+' => Operator::Add,
             '-' => Operator::Sub,
             '*' => Operator::Mul,
             '/' => Operator::Div,
+            '%' => Operator::Mod,
+            '^' => Operator::Pow,
             _ => unreachable!(),
         })
         .map(move |op| Token {
             sym: Sym::Operator(op),
             line: input.location_line(),
-            col: input.get_column() as u32,
+            col: input.get_utf8_column() as u32,
         })
         .parse(input)
 }
 
-fn operator_2(input: Text) -> IResult<Text, Token> {
+fn operator_2(input: Text) -> LexOutput<Token> {
     one_of("<>!=")
         .flat_map(|c| {
             context(
@@ -129,51 +286,188 @@ fn operator_2(input: Text) -> IResult<Text, Token> {
         .map(move |op| Token {
             sym: Sym::Operator(op),
             line: input.location_line(),
-            col: input.get_column() as u32,
+            col: input.get_utf8_column() as u32,
         })
         .parse(input)
 }
 
-fn ident(input: Text) -> IResult<Text, Token> {
-    recognize(pair(alpha1, alphanumeric0))
-        .map(|value: Text| {
-            let sym = if value.fragment().eq_ignore_ascii_case("and") {
-                Sym::Operator(Operator::And)
-            } else if value.fragment().eq_ignore_ascii_case("or") {
-                Sym::Operator(Operator::Or)
-            } else if value.fragment().eq_ignore_ascii_case("xor") {
-                Sym::Operator(Operator::Xor)
-            } else if value.fragment().eq_ignore_ascii_case("not") {
-                Sym::Operator(Operator::Not)
-            } else {
-                Sym::Id(value.fragment())
-            };
+/// Lexes an identifier: a letter or `_` followed by letters, digits or `_`,
+/// letters being the Unicode characters allowed in identifiers (XID).
+fn ident(input: Text) -> LexOutput<Token> {
+    recognize(pair(
+        satisfy(|c| c == '_' || c.is_xid_start()),
+        take_while(|c: char| c.is_xid_continue()),
+    ))
+    .map(|value: Text| {
+        let sym = if value.fragment().eq_ignore_ascii_case("and") {
+            Sym::Operator(Operator::And)
+        } else if value.fragment().eq_ignore_ascii_case("or") {
+            Sym::Operator(Operator::Or)
+        } else if value.fragment().eq_ignore_ascii_case("xor") {
+            Sym::Operator(Operator::Xor)
+        } else if value.fragment().eq_ignore_ascii_case("not") {
+            Sym::Operator(Operator::Not)
+        } else {
+            Sym::Id(value.fragment())
+        };
 
-            Token {
-                sym,
-                line: value.location_line(),
-                col: value.get_column() as u32,
-            }
-        })
-        .parse(input)
+        Token {
+            sym,
+            line: value.location_line(),
+            col: value.get_utf8_column() as u32,
+        }
+    })
+    .parse(input)
 }
 
-fn number(input: Text) -> IResult<Text, Token> {
-    double
-        .map(|value| Token {
-            sym: Sym::Number(value),
-            line: input.location_line(),
-            col: input.get_column() as u32,
-        })
-        .parse(input)
+/// Lexes an identifier quoted with backticks (e.g., `` `content-type` ``),
+/// which can hold any character but a backtick or a line break and is never
+/// a keyword.
+fn quoted_ident(input: Text) -> LexOutput<Token> {
+    let (rest, _) = char('`').parse(input)?;
+
+    match rest.fragment().find(['`', '\n']) {
+        Some(idx) if rest.fragment()[idx..].starts_with('`') => {
+            let (rest, name) = rest.take_split(idx);
+
+            Ok((
+                rest.take_from(1),
+                Token {
+                    sym: Sym::QuotedId(name.fragment()),
+                    line: input.location_line(),
+                    col: input.get_utf8_column() as u32,
+                },
+            ))
+        }
+        _ => Err(LexError::failure(input, LexerError::UnterminatedIdentifier)),
+    }
+}
+
+/// Returns whether a name can be written as an identifier without quotes.
+pub(crate) fn is_ident(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars.next().is_some_and(|c| c == '_' || c.is_xid_start())
+        && chars.all(|c| c.is_xid_continue())
+        && !["and", "or", "xor", "not", "true", "false"]
+            .iter()
+            .any(|keyword| name.eq_ignore_ascii_case(keyword))
+}
+
+/// Lexes an unsigned number, the sign of negative numbers being lexed as an
+/// operator so that `e.a-1` reads as a subtraction. A number must not be
+/// directly followed by a letter (e.g., `12abc`) and must be finite.
+fn number(input: Text) -> LexOutput<Token> {
+    let (rest, value) = recognize((
+        digit1,
+        opt(pair(char('.'), digit1)),
+        opt((one_of("eE"), opt(one_of("+-")), digit1)),
+    ))
+    .parse(input)?;
+
+    let value = value.fragment().parse::<f64>().ok();
+    let glued = rest.fragment().starts_with(|c: char| c.is_xid_continue());
+
+    match value {
+        Some(value) if value.is_finite() && !glued => Ok((
+            rest,
+            Token {
+                sym: Sym::Number(value),
+                line: input.location_line(),
+                col: input.get_utf8_column() as u32,
+            },
+        )),
+        _ => Err(LexError::failure(input, LexerError::InvalidNumber)),
+    }
 }
 
-fn string(input: Text) -> IResult<Text, Token> {
-    delimited(char('"'), take_while(|c| c != '"'), char('"'))
+/// Lexes a string literal, delimited by double or single quotes. The token
+/// keeps the literal as written, the parser decoding it with [`unescape`].
+fn string(input: Text) -> LexOutput<Token> {
+    alt((quoted('"'), quoted('\'')))
         .map(|value: Text| Token {
             sym: Sym::String(value.fragment()),
             line: input.location_line(),
-            col: input.get_column() as u32,
+            col: input.get_utf8_column() as u32,
         })
         .parse(input)
 }
+
+fn quoted(quote: char) -> impl Fn(Text) -> LexOutput<Text> {
+    move |input: Text| {
+        let (rest, _) = char(quote).parse(input)?;
+        let mut chars = rest.fragment().char_indices();
+
+        while let Some((idx, c)) = chars.next() {
+            if c == quote {
+                return Ok(input.take_split(idx + 2 * quote.len_utf8()));
+            }
+
+            if c == '\\' {
+                let Some((_, len)) = escape(&rest.fragment()[idx + 1..]) else {
+                    return Err(LexError::failure(
+                        rest.take_from(idx),
+                        LexerError::InvalidEscape,
+                    ));
+                };
+
+                for _ in 0..len {
+                    chars.next();
+                }
+            }
+        }
+
+        Err(LexError::failure(input, LexerError::UnterminatedString))
+    }
+}
+
+/// Decodes the escape sequence following a backslash, returning the escaped
+/// character and the length of the sequence.
+///
+/// Supported sequences are `\"`, `\'`, `\\`, `\n`, `\r`, `\t` and `\u{...}`,
+/// the latter taking the hexadecimal code point of a Unicode character.
+fn escape(s: &str) -> Option<(char, usize)> {
+    let c = match s.chars().next()? {
+        '"' => '"',
+        '\'' => '\'',
+        '\\' => '\\',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'u' => {
+            let digits = s.strip_prefix("u{")?.split_once('}')?.0;
+
+            if digits.is_empty()
+                || digits.len() > 6
+                || !digits.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                return None;
+            }
+
+            let c = char::from_u32(u32::from_str_radix(digits, 16).ok()?)?;
+            return Some((c, digits.len() + 3));
+        }
+        _ => return None,
+    };
+
+    Some((c, 1))
+}
+
+/// Decodes a string literal lexed by [`tokenize`], quotes included.
+pub(crate) fn unescape(literal: &str) -> String {
+    let inner = &literal[1..literal.len() - 1];
+    let mut value = String::with_capacity(inner.len());
+    let mut rest = inner;
+
+    while let Some(idx) = rest.find('\\') {
+        value.push_str(&rest[..idx]);
+
+        // The lexer only accepts valid escape sequences.
+        let (c, len) = escape(&rest[idx + 1..]).unwrap_or(('\\', 0));
+        value.push(c);
+        rest = &rest[idx + 1 + len..];
+    }
+
+    value.push_str(rest);
+    value
+}
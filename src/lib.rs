@@ -3,28 +3,50 @@
 //! This library provides a complete lexer and parser for EventQL (EQL), a query language
 //! designed for event sourcing systems. It allows you to parse EQL query strings into
 //! an abstract syntax tree (AST) that can be analyzed or executed.
+mod analysis;
 mod ast;
+#[cfg(feature = "eval")]
+mod compile;
+mod constraints;
 mod error;
+#[cfg(feature = "eval")]
+mod eval;
+mod explain;
+mod functions;
 mod lexer;
+mod optimize;
 mod parser;
+mod plan;
+mod schema;
 #[cfg(test)]
 mod tests;
 mod token;
 
-use crate::error::{Error, LexerError};
+use crate::error::Error;
 use crate::prelude::{parse, tokenize};
 pub use ast::*;
-use nom::Err;
 
 /// Convenience module that re-exports all public types and functions.
 ///
 /// This module provides a single import point for all the library's public API,
-/// including AST types, error types, lexer, parser, and token types.
+/// including AST types, error types, functions, lexer, parser, analysis, optimizations, plans, explain, index constraints, schema, token types
+/// and, with the `eval` feature, the query interpreter and expression compiler.
 pub mod prelude {
+    pub use super::analysis::*;
     pub use super::ast::*;
+    #[cfg(feature = "eval")]
+    pub use super::compile::*;
+    pub use super::constraints::*;
     pub use super::error::*;
+    #[cfg(feature = "eval")]
+    pub use super::eval::*;
+    pub use super::explain::*;
+    pub use super::functions::*;
     pub use super::lexer::*;
+    pub use super::optimize::*;
     pub use super::parser::*;
+    pub use super::plan::*;
+    pub use super::schema::*;
     pub use super::token::*;
 }
 
@@ -61,17 +83,7 @@ pub type Result<A> = std::result::Result<A, error::Error>;
 /// }
 /// ```
 pub fn parse_query(input: &str) -> Result<Query> {
-    let tokens = tokenize(input).map_err(|e| match e {
-        Err::Incomplete(_) => Error::Lexer(LexerError::IncompleteInput),
-        Err::Error(x) => Error::Lexer(LexerError::InvalidSymbol(
-            x.input.location_line(),
-            x.input.get_column() as u32,
-        )),
-        Err::Failure(x) => Error::Lexer(LexerError::InvalidSymbol(
-            x.input.location_line(),
-            x.input.get_column() as u32,
-        )),
-    })?;
+    let tokens = tokenize(input).map_err(Error::Lexer)?;
 
     parse(tokens.as_slice()).map_err(Error::Parser)
 }
@@ -0,0 +1,792 @@
+//! Query optimizations for EventQL.
+//!
+//! This module rewrites a [`Query`] into an equivalent one that is cheaper to
+//! run. Rewritten expressions keep the attributes of the expressions they come
+//! from, so that errors still point to the query text and
+//! [`explain`](crate::prelude::explain) can tell where an expression was written.
+//!
+//! # Main Functions
+//!
+//! - [`optimize`] - Apply all the optimizations to a Query
+//! - [`flatten_subqueries`] - Inline subqueries into the queries reading them
+//! - [`push_down_predicates`] - Move filters into the subqueries they depend on
+//! - [`simplify`] - Fold constants and simplify boolean expressions
+use crate::ast::{
+    Access, App, Binary, Expr, Field, GroupBy, OrderBy, Query, SourceKind, Unary, Value,
+};
+use crate::token::Operator;
+
+/// Apply all the optimizations of this module to a query.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::optimize;
+///
+/// let query = parse_query(
+///     "FROM o IN (FROM e IN events PROJECT INTO { price: e.data.price }) \
+///      WHERE o.price > 20 \
+///      PROJECT INTO o",
+/// ).unwrap();
+///
+/// let query = optimize(&query);
+///
+/// assert_eq!(query.sources.len(), 1);
+/// assert_eq!(query.predicate.unwrap().to_string(), "e.data.price > 20");
+/// assert_eq!(query.projection.to_string(), "{price: e.data.price}");
+/// ```
+pub fn optimize(query: &Query) -> Query {
+    simplify_clauses(&push_down_predicates(&flatten_subqueries(query)))
+}
+
+/// Move the parts of WHERE clauses that only depend on a subquery into that
+/// subquery.
+///
+/// A query such as
+///
+/// ```text
+/// FROM o IN (FROM e IN events PROJECT INTO { id: e.id, value: e.data.total })
+/// WHERE o.value > 100 AND o.id != "42"
+/// PROJECT INTO o
+/// ```
+///
+/// is rewritten into
+///
+/// ```text
+/// FROM o IN (FROM e IN events WHERE e.data.total > 100 AND e.id != "42" PROJECT INTO { id: e.id, value: e.data.total })
+/// PROJECT INTO o
+/// ```
+///
+/// so that the subquery produces fewer results. A conjunct of the WHERE clause
+/// is pushed down when it only refers to fields of the subquery results, and
+/// the subquery:
+///
+/// - projects into a record whose fields are simple expressions: literals,
+///   variables, field accesses and operators, but no function calls;
+/// - has no GROUP BY clause nor TOP or SKIP limit, since filtering its rows
+///   before grouping or limiting them changes its results.
+///
+/// Subqueries are optimized recursively.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::{SourceKind, push_down_predicates};
+///
+/// let query = parse_query(
+///     "FROM o IN (FROM e IN events PROJECT INTO { value: e.data.total }) \
+///      WHERE o.value > 100 \
+///      PROJECT INTO o",
+/// ).unwrap();
+/// let query = push_down_predicates(&query);
+///
+/// let SourceKind::Subquery(subquery) = &query.sources[0].kind else {
+///     unreachable!()
+/// };
+///
+/// assert!(query.predicate.is_none());
+/// assert_eq!(subquery.predicate.as_ref().unwrap().to_string(), "e.data.total > 100");
+/// ```
+pub fn push_down_predicates(query: &Query) -> Query {
+    let mut query = query.clone();
+    let mut remaining = Vec::new();
+
+    if let Some(predicate) = query.predicate.take() {
+        for conjunct in conjuncts(predicate) {
+            if !push_down(&mut query, &conjunct) {
+                remaining.push(conjunct);
+            }
+        }
+    }
+
+    query.predicate = remaining.into_iter().reduce(and);
+
+    for source in &mut query.sources {
+        if let SourceKind::Subquery(subquery) = &mut source.kind {
+            **subquery = push_down_predicates(subquery);
+        }
+    }
+
+    query
+}
+
+/// Inline the subqueries that only filter and project their rows into the
+/// queries reading them.
+///
+/// A query such as
+///
+/// ```text
+/// FROM o IN (FROM e IN events WHERE e.type == "order" PROJECT INTO { id: e.id, value: e.data.total })
+/// WHERE o.value > 100
+/// PROJECT INTO { order: o.id }
+/// ```
+///
+/// is rewritten into
+///
+/// ```text
+/// FROM e IN events
+/// WHERE e.type == "order" AND e.data.total > 100
+/// PROJECT INTO { order: e.id }
+/// ```
+///
+/// The sources of the subquery replace it, its WHERE clause is combined with
+/// the one of the query, and the variable bound to its results is replaced with
+/// its projection everywhere in the query. A subquery is inlined when:
+///
+/// - it has no GROUP BY, ORDER BY, TOP or SKIP clause and is not DISTINCT;
+/// - its projection is a simple expression: literals, variables, field accesses
+///   and operators, but no function calls, which would otherwise be evaluated
+///   once per use or lose their aggregate meaning;
+/// - the query accesses existing fields only, when the projection is a record;
+/// - the variables of the subquery do not clash with the other names of the
+///   query.
+///
+/// Nested subqueries are flattened first.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::{SourceKind, flatten_subqueries};
+///
+/// let query = parse_query(
+///     "FROM o IN (FROM e IN events WHERE e.type == \"order\" PROJECT INTO { id: e.id }) \
+///      PROJECT INTO o.id",
+/// ).unwrap();
+/// let query = flatten_subqueries(&query);
+///
+/// assert!(matches!(&query.sources[0].kind, SourceKind::Name(name) if name == "events"));
+/// assert_eq!(query.predicate.unwrap().to_string(), "e.type == \"order\"");
+/// assert_eq!(query.projection.to_string(), "e.id");
+/// ```
+pub fn flatten_subqueries(query: &Query) -> Query {
+    let mut query = query.clone();
+    let mut idx = 0;
+
+    while idx < query.sources.len() {
+        if let SourceKind::Subquery(subquery) = &mut query.sources[idx].kind {
+            **subquery = flatten_subqueries(subquery);
+        }
+
+        idx += inline(&mut query, idx).unwrap_or(1);
+    }
+
+    query
+}
+
+/// Inlines the source at the given index if it is a subquery that can be
+/// inlined, returning the number of sources replacing it.
+fn inline(query: &mut Query, idx: usize) -> Option<usize> {
+    let source = &query.sources[idx];
+    let SourceKind::Subquery(subquery) = &source.kind else {
+        return None;
+    };
+
+    if subquery.group_by.is_some()
+        || subquery.order_by.is_some()
+        || subquery.limit.is_some()
+        || subquery.distinct
+        || !is_simple(&subquery.projection)
+    {
+        return None;
+    }
+
+    let clashes = |name: &str| {
+        query
+            .sources
+            .iter()
+            .enumerate()
+            .any(|(other, source)| other != idx && source.binding == name)
+            || query
+                .group_by
+                .as_ref()
+                .is_some_and(|group_by| group_by.binding.as_deref() == Some(name))
+    };
+
+    if subquery.sources.iter().any(|inner| clashes(&inner.binding)) {
+        return None;
+    }
+
+    let binding = source.binding.as_str();
+    let projection = &subquery.projection;
+    let rewrite = |expr: &Option<Expr>| match expr {
+        Some(expr) => substitute(expr, binding, projection).map(Some),
+        None => Some(None),
+    };
+
+    let predicate = rewrite(&query.predicate)?;
+    let group_by = match &query.group_by {
+        Some(group_by) => Some(GroupBy {
+            expr: substitute(&group_by.expr, binding, projection)?,
+            binding: group_by.binding.clone(),
+            predicate: rewrite(&group_by.predicate)?,
+        }),
+        None => None,
+    };
+    let order_by = match &query.order_by {
+        Some(order_by) => Some(OrderBy {
+            expr: substitute(&order_by.expr, binding, projection)?,
+            order: order_by.order,
+        }),
+        None => None,
+    };
+    let projection = substitute(&query.projection, binding, projection)?;
+
+    let SourceKind::Subquery(subquery) = query.sources.remove(idx).kind else {
+        unreachable!()
+    };
+    let subquery = *subquery;
+    let count = subquery.sources.len();
+
+    query.sources.splice(idx..idx, subquery.sources);
+    query.predicate = match (subquery.predicate, predicate) {
+        (Some(inner), Some(outer)) => Some(and(inner, outer)),
+        (inner, outer) => inner.or(outer),
+    };
+    query.group_by = group_by;
+    query.order_by = order_by;
+    query.projection = projection;
+
+    // Expressions moved out of the subquery now belong to the outer query.
+    let scope = query.attrs.scope;
+    let group_by = query.group_by.as_mut();
+    query
+        .predicate
+        .iter_mut()
+        .chain(group_by.into_iter().flat_map(|group_by| {
+            std::iter::once(&mut group_by.expr).chain(group_by.predicate.as_mut())
+        }))
+        .chain(query.order_by.as_mut().map(|order_by| &mut order_by.expr))
+        .chain(std::iter::once(&mut query.projection))
+        .for_each(|expr| rescope(expr, scope));
+
+    Some(count)
+}
+
+/// Moves an expression, and every expression it contains, to the given scope.
+fn rescope(expr: &mut Expr, scope: u64) {
+    expr.attrs.scope = scope;
+
+    match &mut expr.value {
+        Value::Number(_) | Value::String(_) | Value::Bool(_) | Value::Id(_) => {}
+        Value::Array(elems) => elems.iter_mut().for_each(|elem| rescope(elem, scope)),
+        Value::Record(fields) => fields
+            .iter_mut()
+            .for_each(|field| rescope(&mut field.value, scope)),
+        Value::Access(access) => rescope(&mut access.target, scope),
+        Value::App(app) => app.args.iter_mut().for_each(|arg| rescope(arg, scope)),
+        Value::Binary(binary) => {
+            rescope(&mut binary.lhs, scope);
+            rescope(&mut binary.rhs, scope);
+        }
+        Value::Unary(unary) => rescope(&mut unary.expr, scope),
+        Value::Group(inner) => rescope(inner, scope),
+    }
+}
+
+/// Simplify an expression computing a value, such as a projection.
+///
+/// The simplifier:
+///
+/// - folds arithmetic, comparisons and boolean operations over literals
+///   (`1 + 2 * 3` becomes `7`), except divisions by zero, which are left for
+///   the interpreter to report;
+/// - collapses double negations (`NOT NOT x` becomes `x`, `--x` becomes `x`);
+/// - applies boolean identities: `false AND x` is `false` and `true OR x` is
+///   `true`, while `true AND x` and `false OR x` become `x` when `x` is
+///   known to be a boolean, since `x` could otherwise be `null`;
+/// - strips parentheses that do not change how an expression reads.
+///
+/// A simplified expression keeps the attributes of the expression it replaces,
+/// or of the operand it reduces to, so that errors still point to the query
+/// text. The expression is expected to be well-typed, as checked by
+/// [`analyze`](crate::prelude::analyze): `NOT NOT 42` simplifies to `42`.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::simplify;
+///
+/// let query = parse_query("FROM e IN events PROJECT INTO [(1 + 2) * 3, NOT NOT e.enabled]").unwrap();
+///
+/// assert_eq!(simplify(&query.projection).to_string(), "[9, e.enabled]");
+/// ```
+pub fn simplify(expr: &Expr) -> Expr {
+    Simplifier { predicate: false }.expr(expr)
+}
+
+/// Simplify a predicate, such as a WHERE clause.
+///
+/// Since a `null` predicate is not satisfied, `true AND x` and `false OR x`
+/// become `x` whatever `x` is, on top of the simplifications of [`simplify`].
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::simplify_predicate;
+///
+/// let query = parse_query("FROM e IN events WHERE 1 < 2 AND e.enabled PROJECT INTO e").unwrap();
+///
+/// assert_eq!(simplify_predicate(query.predicate.as_ref().unwrap()).to_string(), "e.enabled");
+/// ```
+pub fn simplify_predicate(expr: &Expr) -> Expr {
+    Simplifier { predicate: true }.expr(expr)
+}
+
+/// Simplifies the expressions of every clause of a query and of its
+/// subqueries, and drops WHERE and HAVING clauses that are always satisfied.
+pub(crate) fn simplify_clauses(query: &Query) -> Query {
+    let mut query = query.clone();
+
+    for source in &mut query.sources {
+        if let SourceKind::Subquery(subquery) = &mut source.kind {
+            **subquery = simplify_clauses(subquery);
+        }
+    }
+
+    query.predicate = query
+        .predicate
+        .as_ref()
+        .map(simplify_predicate)
+        .filter(|predicate| !matches!(predicate.value, Value::Bool(true)));
+
+    if let Some(group_by) = &mut query.group_by {
+        group_by.expr = simplify(&group_by.expr);
+        group_by.predicate = group_by
+            .predicate
+            .as_ref()
+            .map(simplify_predicate)
+            .filter(|predicate| !matches!(predicate.value, Value::Bool(true)));
+    }
+
+    if let Some(order_by) = &mut query.order_by {
+        order_by.expr = simplify(&order_by.expr);
+    }
+
+    query.projection = simplify(&query.projection);
+
+    query
+}
+
+#[derive(Clone, Copy)]
+struct Simplifier {
+    /// Whether the expression being simplified is used as a predicate, in
+    /// which case `null` and `false` are equivalent.
+    predicate: bool,
+}
+
+impl Simplifier {
+    fn expr(self, expr: &Expr) -> Expr {
+        let value = Simplifier { predicate: false };
+        let attrs = || expr.attrs.clone();
+        let literal = |value| Expr {
+            attrs: expr.attrs.clone(),
+            value,
+        };
+
+        match &expr.value {
+            Value::Number(_) | Value::String(_) | Value::Bool(_) | Value::Id(_) => expr.clone(),
+
+            Value::Group(inner) => self.expr(inner),
+
+            Value::Array(elems) => literal(Value::Array(
+                elems.iter().map(|elem| value.expr(elem)).collect(),
+            )),
+
+            Value::Record(fields) => literal(Value::Record(
+                fields
+                    .iter()
+                    .map(|field| Field {
+                        name: field.name.clone(),
+                        value: value.expr(&field.value),
+                    })
+                    .collect(),
+            )),
+
+            Value::Access(access) => literal(Value::Access(Access {
+                target: Box::new(value.operand(&access.target)),
+                field: access.field.clone(),
+            })),
+
+            Value::App(app) => literal(Value::App(App {
+                func: app.func.clone(),
+                args: app.args.iter().map(|arg| value.expr(arg)).collect(),
+            })),
+
+            Value::Unary(unary) => {
+                let operand = value.operand(&unary.expr);
+                let stripped = match &operand.value {
+                    Value::Group(inner) => inner.as_ref(),
+                    _ => &operand,
+                };
+
+                match (unary.operator, &stripped.value) {
+                    (Operator::Not, Value::Bool(b)) => literal(Value::Bool(!b)),
+                    (Operator::Sub, Value::Number(n)) => literal(Value::Number(-n)),
+                    (Operator::Add, Value::Number(_)) => stripped.clone(),
+                    (Operator::Not, Value::Unary(inner)) if inner.operator == Operator::Not => {
+                        strip(*inner.expr.clone())
+                    }
+                    (Operator::Sub, Value::Unary(inner)) if inner.operator == Operator::Sub => {
+                        strip(*inner.expr.clone())
+                    }
+                    _ => Expr {
+                        attrs: attrs(),
+                        value: Value::Unary(Unary {
+                            operator: unary.operator,
+                            expr: Box::new(operand),
+                        }),
+                    },
+                }
+            }
+
+            Value::Binary(binary) => {
+                let operands = match binary.operator {
+                    Operator::And | Operator::Or | Operator::Xor => self,
+                    _ => value,
+                };
+                let lhs = operands.operand(&binary.lhs);
+                let rhs = operands.operand(&binary.rhs);
+
+                if let Some(folded) = fold(binary.operator, &lhs.value, &rhs.value) {
+                    return literal(folded);
+                }
+
+                match (binary.operator, &lhs.value, &rhs.value) {
+                    (Operator::And, Value::Bool(false), _)
+                    | (Operator::And, _, Value::Bool(false)) => literal(Value::Bool(false)),
+                    (Operator::Or, Value::Bool(true), _) | (Operator::Or, _, Value::Bool(true)) => {
+                        literal(Value::Bool(true))
+                    }
+                    (Operator::And, Value::Bool(true), _)
+                    | (Operator::Or, Value::Bool(false), _)
+                        if self.predicate || is_bool(&rhs) =>
+                    {
+                        strip(rhs)
+                    }
+                    (Operator::And, _, Value::Bool(true))
+                    | (Operator::Or, _, Value::Bool(false))
+                        if self.predicate || is_bool(&lhs) =>
+                    {
+                        strip(lhs)
+                    }
+                    _ => Expr {
+                        attrs: attrs(),
+                        value: Value::Binary(Binary {
+                            lhs: Box::new(lhs),
+                            operator: binary.operator,
+                            rhs: Box::new(rhs),
+                        }),
+                    },
+                }
+            }
+        }
+    }
+
+    /// Simplifies the operand of an operator, parenthesizing it if it is a
+    /// binary operation that was parenthesized or that replaced another one,
+    /// or a parenthesized negation (e.g., `(-2) ^ e.n`).
+    fn operand(self, expr: &Expr) -> Expr {
+        let simplified = self.expr(expr);
+
+        let parenthesize = match (&expr.value, &simplified.value) {
+            (Value::Binary(a), Value::Binary(b)) => a.operator != b.operator,
+            (_, Value::Binary(_)) | (Value::Group(_), Value::Unary(_)) => true,
+            (Value::Group(_), Value::Number(n)) => *n < 0.0,
+            _ => false,
+        };
+
+        if !parenthesize {
+            return simplified;
+        }
+
+        Expr {
+            attrs: expr.attrs.clone(),
+            value: Value::Group(Box::new(simplified)),
+        }
+    }
+}
+
+/// Evaluates an operator over two literals, if the interpreter would succeed.
+fn fold(operator: Operator, lhs: &Value, rhs: &Value) -> Option<Value> {
+    let ordering = match (lhs, rhs) {
+        (Value::Number(a), Value::Number(b)) => {
+            let (a, b) = (*a, *b);
+            // Numbers that cannot be written as literals (e.g., `inf`) are
+            // left to the interpreter.
+            let finite = |n: f64| n.is_finite().then_some(Value::Number(n));
+
+            match operator {
+                Operator::Add => return finite(a + b),
+                Operator::Sub => return finite(a - b),
+                Operator::Mul => return finite(a * b),
+                Operator::Div if b != 0.0 => return finite(a / b),
+                Operator::Mod if b != 0.0 => return finite(a % b),
+                Operator::Pow => return finite(a.powf(b)),
+                _ => a.partial_cmp(&b)?,
+            }
+        }
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => match operator {
+            Operator::And => return Some(Value::Bool(*a && *b)),
+            Operator::Or => return Some(Value::Bool(*a || *b)),
+            Operator::Xor => return Some(Value::Bool(a != b)),
+            Operator::Eq | Operator::Neq => a.cmp(b),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(Value::Bool(match operator {
+        Operator::Eq => ordering.is_eq(),
+        Operator::Neq => ordering.is_ne(),
+        Operator::Lt => ordering.is_lt(),
+        Operator::Lte => ordering.is_le(),
+        Operator::Gt => ordering.is_gt(),
+        Operator::Gte => ordering.is_ge(),
+        _ => return None,
+    }))
+}
+
+/// Tells whether an expression always evaluates to a boolean, never `null`.
+fn is_bool(expr: &Expr) -> bool {
+    match &expr.value {
+        Value::Bool(_) => true,
+        Value::Binary(binary) => matches!(
+            binary.operator,
+            Operator::And | Operator::Or | Operator::Xor | Operator::Eq | Operator::Neq
+        ),
+        Value::Group(inner) => is_bool(inner),
+        _ => false,
+    }
+}
+
+/// Removes the parentheses around an expression that is no longer an operand.
+fn strip(expr: Expr) -> Expr {
+    match expr.value {
+        Value::Group(inner) => strip(*inner),
+        _ => expr,
+    }
+}
+
+/// Pushes a conjunct into the subquery it depends on, if any and possible.
+fn push_down(query: &mut Query, conjunct: &Expr) -> bool {
+    let Some(binding) = single_variable(conjunct) else {
+        return false;
+    };
+
+    let Some(SourceKind::Subquery(subquery)) = query
+        .sources
+        .iter_mut()
+        .find(|source| source.binding == binding)
+        .map(|source| &mut source.kind)
+    else {
+        return false;
+    };
+
+    if subquery.group_by.is_some() || subquery.limit.is_some() {
+        return false;
+    }
+
+    if !matches!(subquery.projection.value, Value::Record(_)) || !is_simple(&subquery.projection) {
+        return false;
+    }
+
+    let Some(mut pushed) = substitute(conjunct, &binding, &subquery.projection) else {
+        return false;
+    };
+
+    // A bare condition (e.g., `WHERE o.v`) is replaced by an expression of the
+    // subquery, which must still read as written in the enclosing query.
+    pushed.attrs.scope = conjunct.attrs.scope;
+
+    subquery.predicate = Some(match subquery.predicate.take() {
+        Some(predicate) => and(predicate, pushed),
+        None => pushed,
+    });
+
+    true
+}
+
+/// Splits a predicate into the expressions combined by its top-level ANDs.
+pub(crate) fn conjuncts(expr: Expr) -> Vec<Expr> {
+    match expr.value {
+        Value::Binary(Binary {
+            lhs,
+            operator: Operator::And,
+            rhs,
+        }) => {
+            let mut exprs = conjuncts(*lhs);
+            exprs.extend(conjuncts(*rhs));
+            exprs
+        }
+
+        Value::Group(inner) if is_and(&inner) => conjuncts(*inner),
+
+        value => vec![Expr {
+            attrs: expr.attrs,
+            value,
+        }],
+    }
+}
+
+/// Combines two predicates with AND, parenthesizing operands using operators
+/// that would otherwise read differently.
+pub(crate) fn and(lhs: Expr, rhs: Expr) -> Expr {
+    let attrs = lhs.attrs.clone();
+
+    Expr {
+        attrs,
+        value: Value::Binary(Binary {
+            lhs: Box::new(parenthesize(lhs, is_or)),
+            operator: Operator::And,
+            rhs: Box::new(parenthesize(rhs, is_or)),
+        }),
+    }
+}
+
+fn is_and(expr: &Expr) -> bool {
+    matches!(&expr.value, Value::Binary(binary) if binary.operator == Operator::And)
+}
+
+fn is_or(operator: Operator) -> bool {
+    matches!(operator, Operator::Or | Operator::Xor)
+}
+
+/// Wraps an expression in parentheses if it is a binary operation whose
+/// operator satisfies the given test.
+fn parenthesize(expr: Expr, test: impl Fn(Operator) -> bool) -> Expr {
+    match &expr.value {
+        Value::Binary(binary) if test(binary.operator) => Expr {
+            attrs: expr.attrs.clone(),
+            value: Value::Group(Box::new(expr)),
+        },
+        _ => expr,
+    }
+}
+
+/// Returns the only variable an expression refers to, if it refers to exactly
+/// one.
+fn single_variable(expr: &Expr) -> Option<String> {
+    fn collect<'a>(expr: &'a Expr, names: &mut Vec<&'a str>) {
+        if let Value::Id(name) = &expr.value
+            && !names.contains(&name.as_str())
+        {
+            names.push(name);
+        }
+
+        for child in expr.children() {
+            collect(child, names);
+        }
+    }
+
+    let mut names = Vec::new();
+    collect(expr, &mut names);
+
+    match names.as_slice() {
+        [name] => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Tells whether an expression only uses literals, variables, field accesses
+/// and operators.
+fn is_simple(expr: &Expr) -> bool {
+    match &expr.value {
+        Value::App(_) => false,
+        _ => expr.children().all(is_simple),
+    }
+}
+
+/// Rewrites an expression in terms of the projection of a subquery bound to
+/// `binding`: accesses to `binding.field` are replaced with the expression of
+/// that field when the projection is a record, and other uses of the variable
+/// with the projection itself.
+///
+/// Returns `None` if the expression refers to a field the projection does not
+/// have.
+fn substitute(expr: &Expr, binding: &str, projection: &Expr) -> Option<Expr> {
+    let value = match &expr.value {
+        Value::Id(name) if name == binding => return Some(operand(projection.clone())),
+
+        Value::Number(_) | Value::String(_) | Value::Bool(_) | Value::Id(_) => expr.value.clone(),
+
+        Value::Access(access) => {
+            if let Value::Id(name) = &access.target.value
+                && name == binding
+                && let Value::Record(fields) = &projection.value
+            {
+                let field = fields.iter().find(|field| field.name == access.field)?;
+
+                return Some(operand(field.value.clone()));
+            }
+
+            Value::Access(Access {
+                target: Box::new(substitute(&access.target, binding, projection)?),
+                field: access.field.clone(),
+            })
+        }
+
+        Value::Array(elems) => Value::Array(
+            elems
+                .iter()
+                .map(|elem| substitute(elem, binding, projection))
+                .collect::<Option<_>>()?,
+        ),
+
+        Value::Record(fields) => Value::Record(
+            fields
+                .iter()
+                .map(|field| {
+                    Some(Field {
+                        name: field.name.clone(),
+                        value: substitute(&field.value, binding, projection)?,
+                    })
+                })
+                .collect::<Option<_>>()?,
+        ),
+
+        Value::App(app) => Value::App(App {
+            func: app.func.clone(),
+            args: app
+                .args
+                .iter()
+                .map(|arg| substitute(arg, binding, projection))
+                .collect::<Option<_>>()?,
+        }),
+
+        Value::Binary(binary) => Value::Binary(Binary {
+            lhs: Box::new(substitute(&binary.lhs, binding, projection)?),
+            operator: binary.operator,
+            rhs: Box::new(substitute(&binary.rhs, binding, projection)?),
+        }),
+
+        Value::Unary(unary) => Value::Unary(Unary {
+            operator: unary.operator,
+            expr: Box::new(substitute(&unary.expr, binding, projection)?),
+        }),
+
+        Value::Group(inner) => Value::Group(Box::new(substitute(inner, binding, projection)?)),
+    };
+
+    Some(Expr {
+        attrs: expr.attrs.clone(),
+        value,
+    })
+}
+
+/// Wraps an expression substituted for a variable in parentheses if it is an
+/// operation, so that it reads as a single operand.
+fn operand(expr: Expr) -> Expr {
+    match &expr.value {
+        Value::Binary(_) | Value::Unary(_) => Expr {
+            attrs: expr.attrs.clone(),
+            value: Value::Group(Box::new(expr)),
+        },
+        _ => expr,
+    }
+}
@@ -13,6 +13,7 @@ use crate::ast::{
     Unary, Value,
 };
 use crate::error::ParserError;
+use crate::lexer::unescape;
 use crate::token::{Operator, Sym, Symbol, Token};
 
 /// Result type for parser operations.
@@ -28,11 +29,14 @@ struct Parser<'a> {
 
 impl<'a> Parser<'a> {
     fn new(input: &'a [Token<'a>]) -> Self {
-        Self {
+        let mut parser = Self {
             input,
             offset: 0,
             scope: 0,
-        }
+        };
+
+        parser.skip_comments();
+        parser
     }
 
     fn peek<'b>(&'b self) -> Token<'a> {
@@ -44,15 +48,25 @@ impl<'a> Parser<'a> {
 
         if self.offset + 1 < self.input.len() {
             self.offset += 1;
+            self.skip_comments();
         }
 
         res
     }
 
+    /// Skips the comments kept by [`tokenize_with_comments`](crate::prelude::tokenize_with_comments).
+    fn skip_comments(&mut self) {
+        while matches!(self.input[self.offset].sym, Sym::Comment(_))
+            && self.offset + 1 < self.input.len()
+        {
+            self.offset += 1;
+        }
+    }
+
     fn parse_ident(&mut self) -> ParseResult<String> {
         let token = self.shift();
 
-        if let Sym::Id(id) = token.sym {
+        if let Sym::Id(id) | Sym::QuotedId(id) = token.sym {
             return Ok(id.to_owned());
         }
 
@@ -66,8 +80,8 @@ impl<'a> Parser<'a> {
     fn parse_source_kind(&mut self) -> ParseResult<SourceKind> {
         let token = self.shift();
         match token.sym {
-            Sym::Id(id) => Ok(SourceKind::Name(id.to_owned())),
-            Sym::String(sub) => Ok(SourceKind::Subject(sub.to_owned())),
+            Sym::Id(id) | Sym::QuotedId(id) => Ok(SourceKind::Name(id.to_owned())),
+            Sym::String(sub) => Ok(SourceKind::Subject(unescape(sub))),
             Sym::Symbol(Symbol::OpenParen) => {
                 let query = self.parse_query()?;
                 expect_symbol(self.shift(), Symbol::CloseParen)?;
@@ -84,11 +98,12 @@ impl<'a> Parser<'a> {
 
     fn parse_source(&mut self) -> ParseResult<Source> {
         expect_keyword(self.shift(), "from")?;
+        let pos = self.peek().into();
         let binding = self.parse_ident()?;
         expect_keyword(self.shift(), "in")?;
         let kind = self.parse_source_kind()?;
 
-        Ok(Source { binding, kind })
+        Ok(Source { pos, binding, kind })
     }
 
     fn parse_where_clause(&mut self) -> ParseResult<Expr> {
@@ -96,12 +111,28 @@ impl<'a> Parser<'a> {
         self.parse_expr()
     }
 
-    fn parse_group_by(&mut self) -> ParseResult<GroupBy> {
+    /// Parses a GROUP BY clause. A group key named after its last field is
+    /// left unnamed when that name is already bound by a source of the query.
+    fn parse_group_by(&mut self, sources: &[Source]) -> ParseResult<GroupBy> {
         expect_keyword(self.shift(), "group")?;
         expect_keyword(self.shift(), "by")?;
 
         let expr = self.parse_expr()?;
 
+        let binding = if let Sym::Id(name) = self.peek().sym
+            && name.eq_ignore_ascii_case("as")
+        {
+            self.shift();
+
+            Some(self.parse_ident()?)
+        } else if let Value::Access(access) = &expr.value
+            && !sources.iter().any(|source| source.binding == access.field)
+        {
+            Some(access.field.clone())
+        } else {
+            None
+        };
+
         let predicate = if let Sym::Id(name) = self.peek().sym
             && name.eq_ignore_ascii_case("having")
         {
@@ -112,7 +143,11 @@ impl<'a> Parser<'a> {
             None
         };
 
-        Ok(GroupBy { expr, predicate })
+        Ok(GroupBy {
+            expr,
+            binding,
+            predicate,
+        })
     }
 
     fn parse_order_by(&mut self) -> ParseResult<OrderBy> {
@@ -179,6 +214,7 @@ impl<'a> Parser<'a> {
             Sym::Eof => Err(ParserError::UnexpectedEof),
 
             Sym::Id(_)
+            | Sym::QuotedId(_)
             | Sym::String(_)
             | Sym::Number(_)
             | Sym::Symbol(Symbol::OpenParen | Symbol::OpenBracket | Symbol::OpenBrace)
@@ -221,34 +257,14 @@ impl<'a> Parser<'a> {
                         func: name.to_owned(),
                         args,
                     })
-                } else if matches!(self.peek().sym, Sym::Symbol(Symbol::Dot)) {
-                    self.shift();
-                    let mut access = Access {
-                        target: Box::new(Expr {
-                            attrs: Attrs::new(token.into(), self.scope),
-                            value: Value::Id(name.to_owned()),
-                        }),
-                        field: self.parse_ident()?,
-                    };
-
-                    while matches!(self.peek().sym, Sym::Symbol(Symbol::Dot)) {
-                        self.shift();
-                        access = Access {
-                            target: Box::new(Expr {
-                                attrs: access.target.attrs,
-                                value: Value::Access(access),
-                            }),
-                            field: self.parse_ident()?,
-                        };
-                    }
-
-                    Value::Access(access)
                 } else {
-                    Value::Id(name.to_owned())
+                    self.parse_variable(token, name)?
                 }
             }
 
-            Sym::String(s) => Value::String(s.to_owned()),
+            Sym::QuotedId(name) => self.parse_variable(token, name)?,
+
+            Sym::String(s) => Value::String(unescape(s)),
             Sym::Number(n) => Value::Number(n),
 
             Sym::Symbol(Symbol::OpenParen) => {
@@ -302,10 +318,17 @@ impl<'a> Parser<'a> {
             }
 
             Sym::Operator(op) if matches!(op, Operator::Add | Operator::Sub | Operator::Not) => {
-                Value::Unary(Unary {
-                    operator: op,
-                    expr: Box::new(self.parse_expr()?),
-                })
+                let expr = self.parse_binary(prefix_binding_pow(op))?;
+
+                // The lexer only produces unsigned numbers: negative literals are
+                // built here.
+                match expr.value {
+                    Value::Number(n) if op == Operator::Sub => Value::Number(-n),
+                    _ => Value::Unary(Unary {
+                        operator: op,
+                        expr: Box::new(expr),
+                    }),
+                }
             }
 
             _ => {
@@ -323,15 +346,43 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// Parses a variable, or the fields accessed through it (e.g., `e.data.price`).
+    fn parse_variable(&mut self, token: Token, name: &str) -> ParseResult<Value> {
+        if !matches!(self.peek().sym, Sym::Symbol(Symbol::Dot)) {
+            return Ok(Value::Id(name.to_owned()));
+        }
+
+        self.shift();
+        let mut access = Access {
+            target: Box::new(Expr {
+                attrs: Attrs::new(token.into(), self.scope),
+                value: Value::Id(name.to_owned()),
+            }),
+            field: self.parse_ident()?,
+        };
+
+        while matches!(self.peek().sym, Sym::Symbol(Symbol::Dot)) {
+            self.shift();
+            access = Access {
+                target: Box::new(Expr {
+                    attrs: access.target.attrs.clone(),
+                    value: Value::Access(access),
+                }),
+                field: self.parse_ident()?,
+            };
+        }
+
+        Ok(Value::Access(access))
+    }
+
     fn parse_binary(&mut self, min_bind: u64) -> ParseResult<Expr> {
         let mut lhs = self.parse_primary()?;
 
         loop {
             let token = self.peek();
-            let operator = if let Sym::Operator(op) = token.sym {
-                op
-            } else {
-                break;
+            let operator = match token.sym {
+                Sym::Operator(op) if op != Operator::Not => op,
+                _ => break,
             };
 
             let (lhs_bind, rhs_bind) = binding_pow(operator);
@@ -344,7 +395,7 @@ impl<'a> Parser<'a> {
             let rhs = self.parse_binary(rhs_bind)?;
 
             lhs = Expr {
-                attrs: lhs.attrs,
+                attrs: lhs.attrs.clone(),
                 value: Value::Binary(Binary {
                     lhs: Box::new(lhs),
                     operator,
@@ -380,7 +431,7 @@ impl<'a> Parser<'a> {
         let group_by = if let Sym::Id(name) = self.peek().sym
             && name.eq_ignore_ascii_case("group")
         {
-            Some(self.parse_group_by()?)
+            Some(self.parse_group_by(&sources)?)
         } else {
             None
         };
@@ -464,14 +515,26 @@ fn expect_symbol(token: Token, expect: Symbol) -> ParseResult<()> {
 fn binding_pow(op: Operator) -> (u64, u64) {
     match op {
         Operator::Add | Operator::Sub => (20, 21),
-        Operator::Mul | Operator::Div => (30, 31),
+        Operator::Mul | Operator::Div | Operator::Mod => (30, 31),
+        Operator::Pow => (51, 50),
         Operator::Eq
         | Operator::Neq
         | Operator::Gt
         | Operator::Lt
         | Operator::Gte
         | Operator::Lte => (10, 11),
-        Operator::And | Operator::Or | Operator::Xor | Operator::Not => (1, 2),
+        Operator::And => (5, 6),
+        Operator::Xor => (3, 4),
+        Operator::Or | Operator::Not => (1, 2),
+    }
+}
+
+/// Binding power of the operand of a unary operator: `NOT` applies to a
+/// comparison, while `-` and `+` apply to the closest operand.
+fn prefix_binding_pow(op: Operator) -> u64 {
+    match op {
+        Operator::Not => 7,
+        _ => 40,
     }
 }
 
@@ -489,7 +552,7 @@ fn binding_pow(op: Operator) -> (u64, u64) {
 /// FROM      := "FROM" Id "IN" SourceKind
 /// SourceKind := Id | String | "(" Query ")"
 /// WHERE     := "WHERE" Expr
-/// GROUP_BY  := "GROUP" "BY" Expr
+/// GROUP_BY  := "GROUP" "BY" Expr ("AS" Id)? ("HAVING" Expr)?
 /// ORDER_BY  := "ORDER" "BY" Expr ("ASC" | "DESC")
 /// LIMIT     := ("TOP" | "SKIP") Number
 /// PROJECT   := "PROJECT" "INTO" Expr
@@ -502,11 +565,15 @@ fn binding_pow(op: Operator) -> (u64, u64) {
 /// The parser uses a Pratt parser for expressions with the following precedence
 /// (from highest to lowest):
 ///
-/// 1. Unary operators (`+`, `-`, `NOT`)
-/// 2. Multiplicative (`*`, `/`)
-/// 3. Additive (`+`, `-`)
-/// 4. Comparison (`<`, `<=`, `>`, `>=`, `==`, `!=`)
-/// 5. Logical (`AND`, `OR`, `XOR`)
+/// 1. Exponentiation (`^`), right-associative: `-2 ^ 2` is `-(2 ^ 2)`
+/// 2. Unary arithmetic operators (`+`, `-`)
+/// 3. Multiplicative (`*`, `/`, `%`)
+/// 4. Additive (`+`, `-`)
+/// 5. Comparison (`<`, `<=`, `>`, `>=`, `==`, `!=`)
+/// 6. Logical negation (`NOT`)
+/// 7. Logical AND (`AND`)
+/// 8. Logical XOR (`XOR`)
+/// 9. Logical OR (`OR`)
 pub fn parse<'a>(input: &'a [Token<'a>]) -> ParseResult<Query> {
     let mut parser = Parser::new(input);
 
@@ -0,0 +1,338 @@
+//! Logical query plans for EventQL.
+//!
+//! A [`Query`] mirrors the clauses of the query text. This module lowers it into
+//! a [`Plan`]: a tree of relational operators, each consuming the rows produced
+//! by its input. The plan spells out the order in which clauses apply, so that
+//! optimizers, explainers and interpreters do not need to know it.
+//!
+//! A query is lowered into the following operators, from the leaves up:
+//!
+//! 1. `FROM`: a scan per source, combined by cross joins
+//! 2. `WHERE`: a filter
+//! 3. `GROUP BY`: an aggregate, followed by a filter for `HAVING`
+//! 4. `ORDER BY`: a sort
+//! 5. `PROJECT INTO`: a projection, optionally followed by a distinct
+//! 6. `TOP`/`SKIP`: a limit
+//!
+//! A query whose projection uses an aggregate function without a GROUP BY
+//! clause gets an aggregate without key, treating all its rows as a single
+//! group.
+//!
+//! # Main Functions
+//!
+//! - [`lower`] - Lower a Query into a logical Plan
+//! - [`lower_with_functions`] - Lower a Query, recognizing custom aggregate functions
+use crate::ast::{Expr, Ident, Limit, Order, Query, Quoted, SourceKind};
+use crate::functions::FunctionRegistry;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// What a [`Plan::Scan`] reads.
+#[derive(Debug, Clone, Serialize)]
+pub enum ScanSource {
+    /// Named source (e.g., `events`)
+    Name(String),
+    /// Events whose subject is the pattern or lies beneath it (e.g., `"/books"`)
+    Subject(String),
+}
+
+/// A logical query plan.
+///
+/// Every operator but the leaves (unit and scans) has one or two inputs. The rows of a plan bind
+/// the variables of the FROM clauses of its query, until the projection turns
+/// each of them into a result.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::lower;
+///
+/// let query = parse_query(
+///     "FROM e IN events WHERE e.data.price > 20 TOP 10 PROJECT INTO e.id"
+/// ).unwrap();
+///
+/// assert_eq!(
+///     lower(&query).to_string(),
+///     "Limit TOP 10\n  Project e.id\n    Filter e.data.price > 20\n      Scan e IN events\n",
+/// );
+/// ```
+#[derive(Debug, Clone, Serialize)]
+pub enum Plan {
+    /// Produces a single row binding no variable, for queries without FROM clause
+    Unit,
+
+    /// Binds each event of a source to a variable
+    Scan {
+        /// Variable bound to the events
+        binding: String,
+        /// Events to read
+        source: ScanSource,
+    },
+
+    /// Binds each result of a subquery to a variable
+    SubqueryScan {
+        /// Variable bound to the results
+        binding: String,
+        /// Scope of the subquery, see [`Attrs::scope`](crate::Attrs::scope)
+        scope: u64,
+        /// Plan of the subquery
+        input: Box<Plan>,
+    },
+
+    /// Combines every row of its left input with every row of its right input
+    CrossJoin {
+        /// Left input
+        lhs: Box<Plan>,
+        /// Right input
+        rhs: Box<Plan>,
+    },
+
+    /// Keeps the rows for which the predicate is `true`
+    Filter {
+        /// Input rows
+        input: Box<Plan>,
+        /// Predicate rows must satisfy
+        predicate: Expr,
+    },
+
+    /// Groups rows by key, or all together without key
+    ///
+    /// Each group is represented by its first row, extended with the name bound
+    /// to the group key. Aggregate functions evaluated on such a row compute
+    /// their value over all the rows of the group.
+    Aggregate {
+        /// Input rows
+        input: Box<Plan>,
+        /// Expression to group by
+        key: Option<Expr>,
+        /// Name bound to the group key, if any
+        binding: Option<String>,
+    },
+
+    /// Sorts rows
+    Sort {
+        /// Input rows
+        input: Box<Plan>,
+        /// Expression to sort by
+        expr: Expr,
+        /// Sort direction
+        order: Order,
+    },
+
+    /// Turns each row into a result
+    Project {
+        /// Input rows
+        input: Box<Plan>,
+        /// Expression computing the result of a row
+        expr: Expr,
+    },
+
+    /// Discards duplicated results
+    Distinct {
+        /// Input results
+        input: Box<Plan>,
+    },
+
+    /// Takes or skips the first results
+    Limit {
+        /// Input results
+        input: Box<Plan>,
+        /// Number of results to take or skip
+        limit: Limit,
+    },
+}
+
+impl Plan {
+    /// Returns the inputs of this operator.
+    pub fn inputs(&self) -> Vec<&Plan> {
+        match self {
+            Plan::Unit | Plan::Scan { .. } => vec![],
+            Plan::CrossJoin { lhs, rhs } => vec![lhs, rhs],
+            Plan::SubqueryScan { input, .. }
+            | Plan::Filter { input, .. }
+            | Plan::Aggregate { input, .. }
+            | Plan::Sort { input, .. }
+            | Plan::Project { input, .. }
+            | Plan::Distinct { input }
+            | Plan::Limit { input, .. } => vec![input],
+        }
+    }
+
+    /// Returns the name of this operator (e.g., `Filter`).
+    pub fn name(&self) -> &'static str {
+        match self {
+            Plan::Unit => "Unit",
+            Plan::Scan { .. } => "Scan",
+            Plan::SubqueryScan { .. } => "SubqueryScan",
+            Plan::CrossJoin { .. } => "CrossJoin",
+            Plan::Filter { .. } => "Filter",
+            Plan::Aggregate { .. } => "Aggregate",
+            Plan::Sort { .. } => "Sort",
+            Plan::Project { .. } => "Project",
+            Plan::Distinct { .. } => "Distinct",
+            Plan::Limit { .. } => "Limit",
+        }
+    }
+
+    /// Returns the arguments of this operator in EventQL syntax (e.g.,
+    /// `e.price > 100` for a filter), or an empty string if it has none.
+    pub fn detail(&self) -> String {
+        match self {
+            Plan::Unit | Plan::CrossJoin { .. } | Plan::Distinct { .. } => String::new(),
+            Plan::Scan {
+                binding,
+                source: ScanSource::Name(name),
+            } => format!("{} IN {}", Ident(binding), Ident(name)),
+            Plan::Scan {
+                binding,
+                source: ScanSource::Subject(pattern),
+            } => format!("{} IN {}", Ident(binding), Quoted(pattern)),
+            Plan::SubqueryScan { binding, .. } => Ident(binding).to_string(),
+            Plan::Filter { predicate, .. } => predicate.to_string(),
+            Plan::Aggregate { key: None, .. } => String::new(),
+            Plan::Aggregate {
+                key: Some(key),
+                binding: None,
+                ..
+            } => format!("BY {}", key),
+            Plan::Aggregate {
+                key: Some(key),
+                binding: Some(binding),
+                ..
+            } => format!("BY {} AS {}", key, Ident(binding)),
+            Plan::Sort { expr, order, .. } => format!("{} {}", expr, order),
+            Plan::Project { expr, .. } => expr.to_string(),
+            Plan::Limit { limit, .. } => limit.to_string(),
+        }
+    }
+
+    fn fmt_node(&self, f: &mut Formatter<'_>, depth: usize) -> std::fmt::Result {
+        write!(f, "{:indent$}{}", "", self.name(), indent = depth * 2)?;
+
+        let detail = self.detail();
+        if !detail.is_empty() {
+            write!(f, " {}", detail)?;
+        }
+
+        writeln!(f)?;
+
+        for input in self.inputs() {
+            input.fmt_node(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Plan {
+    /// Writes the plan as a tree, one operator per line, inputs being indented
+    /// beneath the operator consuming them.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_node(f, 0)
+    }
+}
+
+/// Lower a query into a logical plan.
+///
+/// Aggregate functions are recognized from the built-in functions. Use
+/// [`lower_with_functions`] when the query calls custom aggregate functions.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::parse_query;
+/// use eventql_parser::prelude::{Plan, lower};
+///
+/// let query = parse_query("FROM e IN events PROJECT INTO { total: COUNT() }").unwrap();
+///
+/// assert!(matches!(
+///     lower(&query),
+///     Plan::Project { input, .. } if matches!(*input, Plan::Aggregate { key: None, .. })
+/// ));
+/// ```
+pub fn lower(query: &Query) -> Plan {
+    lower_with_functions(query, &FunctionRegistry::default())
+}
+
+/// Lower a query into a logical plan, recognizing the aggregate functions of
+/// the given registry.
+pub fn lower_with_functions(query: &Query, functions: &FunctionRegistry) -> Plan {
+    let mut sources = query.sources.iter().map(|source| match &source.kind {
+        SourceKind::Name(name) => Plan::Scan {
+            binding: source.binding.clone(),
+            source: ScanSource::Name(name.clone()),
+        },
+        SourceKind::Subject(pattern) => Plan::Scan {
+            binding: source.binding.clone(),
+            source: ScanSource::Subject(pattern.clone()),
+        },
+        SourceKind::Subquery(query) => Plan::SubqueryScan {
+            binding: source.binding.clone(),
+            scope: query.attrs.scope,
+            input: Box::new(lower_with_functions(query, functions)),
+        },
+    });
+
+    let first = sources.next().unwrap_or(Plan::Unit);
+    let mut plan = sources.fold(first, |lhs, rhs| Plan::CrossJoin {
+        lhs: Box::new(lhs),
+        rhs: Box::new(rhs),
+    });
+
+    if let Some(predicate) = &query.predicate {
+        plan = Plan::Filter {
+            input: Box::new(plan),
+            predicate: predicate.clone(),
+        };
+    }
+
+    if let Some(group_by) = &query.group_by {
+        plan = Plan::Aggregate {
+            input: Box::new(plan),
+            key: Some(group_by.expr.clone()),
+            binding: group_by.binding.clone(),
+        };
+
+        if let Some(predicate) = &group_by.predicate {
+            plan = Plan::Filter {
+                input: Box::new(plan),
+                predicate: predicate.clone(),
+            };
+        }
+    } else if functions.has_aggregate(&query.projection) {
+        plan = Plan::Aggregate {
+            input: Box::new(plan),
+            key: None,
+            binding: None,
+        };
+    }
+
+    if let Some(order_by) = &query.order_by {
+        plan = Plan::Sort {
+            input: Box::new(plan),
+            expr: order_by.expr.clone(),
+            order: order_by.order,
+        };
+    }
+
+    plan = Plan::Project {
+        input: Box::new(plan),
+        expr: query.projection.clone(),
+    };
+
+    if query.distinct {
+        plan = Plan::Distinct {
+            input: Box::new(plan),
+        };
+    }
+
+    if let Some(limit) = query.limit {
+        plan = Plan::Limit {
+            input: Box::new(plan),
+            limit,
+        };
+    }
+
+    plan
+}
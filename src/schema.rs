@@ -0,0 +1,77 @@
+//! Schemas of the data queried by EventQL.
+//!
+//! This module describes the shape of the events returned by the built-in
+//! `events` source. EventSourcingDB stores events following the CloudEvents
+//! specification, which means every event shares the same envelope and only
+//! its `data` payload varies.
+//!
+//! # Main Functions
+//!
+//! - [`event_envelope`] - The type of an event as seen from a query
+//! - [`event_envelope_with_data`] - The type of an event whose payload has a known shape
+use crate::ast::Type;
+use std::collections::BTreeMap;
+
+/// Name of the built-in source returning every stored event.
+pub const EVENTS_SOURCE: &str = "events";
+
+/// Returns the type of an event stored in EventSourcingDB.
+///
+/// Every field of the envelope is a string, except `data`, whose shape depends
+/// on the type of the event and is therefore [`Type::Any`]. Tracing and
+/// signature fields are only present on some events and are optional.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::Type;
+/// use eventql_parser::prelude::event_envelope;
+///
+/// let Type::Record(fields) = event_envelope() else {
+///     unreachable!()
+/// };
+///
+/// assert_eq!(fields.get("subject"), Some(&Type::String));
+/// assert_eq!(fields.get("data"), Some(&Type::Any));
+/// assert_eq!(fields.get("tyep"), None);
+/// ```
+pub fn event_envelope() -> Type {
+    event_envelope_with_data(Type::Any)
+}
+
+/// Returns the type of an event stored in EventSourcingDB whose `data` payload
+/// has the given type.
+///
+/// # Examples
+///
+/// ```
+/// use eventql_parser::Type;
+/// use eventql_parser::prelude::event_envelope_with_data;
+/// use std::collections::BTreeMap;
+///
+/// let book = Type::Record(BTreeMap::from([("title".to_string(), Type::String)]));
+/// let Type::Record(fields) = event_envelope_with_data(book.clone()) else {
+///     unreachable!()
+/// };
+///
+/// assert_eq!(fields.get("data"), Some(&book));
+/// ```
+pub fn event_envelope_with_data(data: Type) -> Type {
+    let optional_string = || Type::Optional(Box::new(Type::String));
+
+    Type::Record(BTreeMap::from([
+        ("specversion".to_string(), Type::String),
+        ("id".to_string(), Type::String),
+        ("time".to_string(), Type::String),
+        ("source".to_string(), Type::String),
+        ("subject".to_string(), Type::String),
+        ("type".to_string(), Type::String),
+        ("datacontenttype".to_string(), Type::String),
+        ("data".to_string(), data),
+        ("predecessorhash".to_string(), Type::String),
+        ("hash".to_string(), Type::String),
+        ("traceparent".to_string(), optional_string()),
+        ("tracestate".to_string(), optional_string()),
+        ("signature".to_string(), optional_string()),
+    ]))
+}
@@ -0,0 +1,411 @@
+use crate::analysis::{AnalysisOptions, analyze, analyze_with_options};
+use crate::ast::Type;
+use crate::error::{AnalysisError, Error};
+use crate::functions::FunctionSig;
+use crate::lexer::tokenize;
+use crate::parser::parse;
+use std::collections::BTreeMap;
+
+#[test]
+fn test_analyze_subquery() {
+    let tokens = tokenize(include_str!("./resources/rename_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(analyze(&query).is_ok());
+}
+
+#[test]
+fn test_analysis_error_into_error() {
+    let tokens = tokenize("FROM e IN events WHERE 42 PROJECT INTO e").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+    let err: Error = analyze(&query).unwrap_err().into();
+
+    assert!(matches!(err, Error::Analysis(_)));
+}
+
+#[test]
+fn test_analyze_non_existing_variable() {
+    let tokens = tokenize(include_str!("./resources/rename_non_existing_variable.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UnknownVariable(2, 7, name)) if name == "f"
+    ));
+}
+
+#[test]
+fn test_analyze_duplicate_variable_names() {
+    let tokens = tokenize(include_str!(
+        "./resources/rename_duplicate_variable_names.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::DuplicateBinding(2, 6, name)) if name == "e"
+    ));
+}
+
+#[test]
+fn test_analyze_outer_variable_not_visible_in_subquery() {
+    let tokens =
+        tokenize("FROM e IN (FROM f IN events WHERE e.id == 1 PROJECT INTO f) PROJECT INTO e")
+            .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UnknownVariable(1, 35, name)) if name == "e"
+    ));
+}
+
+#[test]
+fn test_infer_from_events_nested_data() {
+    let tokens = tokenize(include_str!("./resources/from_events_nested_data.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(analyze(&query).unwrap());
+}
+
+#[test]
+fn test_infer_from_events_using_subquery() {
+    let tokens = tokenize(include_str!("./resources/from_events_using_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(analyze(&query).unwrap());
+}
+
+#[test]
+fn test_infer_wrong_where_clause_1() {
+    let tokens = tokenize(include_str!("./resources/infer_wrong_where_clause_1.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::TypeMismatch(2, 7, Type::Bool, Type::Number))
+    ));
+}
+
+#[test]
+fn test_infer_wrong_where_clause_2() {
+    let tokens = tokenize(include_str!("./resources/infer_wrong_where_clause_2.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::TypeMismatch(
+            2,
+            48,
+            Type::String,
+            Type::Number
+        ))
+    ));
+}
+
+#[test]
+fn test_infer_subquery_projection_shape() {
+    let tokens = tokenize(
+        "FROM e IN (FROM e IN events PROJECT INTO { name: \"foo\", tags: [1, 2] }) WHERE e.name > 1 PROJECT INTO e",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::TypeMismatch(
+            1,
+            88,
+            Type::String,
+            Type::Number
+        ))
+    ));
+}
+
+#[test]
+fn test_infer_subquery_unknown_field() {
+    let tokens = tokenize(
+        "FROM e IN (FROM e IN events PROJECT INTO { name: e.subject }) WHERE e.nmae == \"foo\" PROJECT INTO e",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UnknownField(1, 69, field, _)) if field == "nmae"
+    ));
+}
+
+#[test]
+fn test_infer_heterogeneous_array() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO [1, \"two\"]").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::TypeMismatch(
+            1,
+            35,
+            Type::Number,
+            Type::String
+        ))
+    ));
+}
+
+#[test]
+fn test_infer_from_events_with_type_to_project_record() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_with_type_to_project_record.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(analyze(&query).unwrap());
+}
+
+#[test]
+fn test_infer_misspelled_envelope_field() {
+    let tokens =
+        tokenize("FROM e IN events WHERE e.tyep == \"book-acquired\" PROJECT INTO e").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UnknownField(1, 24, field, _)) if field == "tyep"
+    ));
+}
+
+fn book_acquired_options() -> AnalysisOptions {
+    let mut options = AnalysisOptions::default();
+
+    options.register_data_schema(
+        "io.eventsourcingdb.library.book-acquired",
+        Type::Record(BTreeMap::from([
+            ("title".to_string(), Type::String),
+            ("total".to_string(), Type::Number),
+        ])),
+    );
+
+    options
+}
+
+#[test]
+fn test_infer_from_events_using_subquery_with_data_schema() {
+    let tokens = tokenize(include_str!("./resources/from_events_using_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(analyze_with_options(&query, &book_acquired_options()).unwrap());
+}
+
+#[test]
+fn test_infer_data_schema_unknown_field() {
+    let tokens = tokenize(include_str!("./resources/rename_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze_with_options(&query, &book_acquired_options()),
+        Err(AnalysisError::UnknownField(4, 27, field, _)) if field == "foobar"
+    ));
+}
+
+#[test]
+fn test_infer_data_schema_ignored_without_type_filter() {
+    let tokens = tokenize(
+        "FROM e IN events WHERE e.type != \"io.eventsourcingdb.library.book-acquired\" AND e.data.total == \"foo\" PROJECT INTO e",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(analyze_with_options(&query, &book_acquired_options()).is_ok());
+}
+
+#[test]
+fn test_infer_from_events_where_subject_project_record_with_count() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_where_subject_project_record_with_count.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(analyze(&query).unwrap());
+}
+
+#[test]
+fn test_infer_unknown_function() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO { total: CUONT() }").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UnknownFunction(1, 40, name)) if name == "CUONT"
+    ));
+}
+
+#[test]
+fn test_infer_function_wrong_arity() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO { total: cOuNt(1, 2, 3) }").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::WrongArity(1, 40, name, 0, 3)) if name == "cOuNt"
+    ));
+}
+
+#[test]
+fn test_infer_function_wrong_argument_type() {
+    let tokens =
+        tokenize("FROM e IN events PROJECT INTO { name: UPPER(e.data.count + 1) }").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::TypeMismatch(
+            1,
+            45,
+            Type::String,
+            Type::Number
+        ))
+    ));
+}
+
+#[test]
+fn test_infer_custom_function() {
+    let tokens = tokenize("FROM e IN events WHERE ISVIP(e.data.customer) PROJECT INTO e").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+    let mut options = AnalysisOptions::default();
+
+    options
+        .functions
+        .register("isvip", FunctionSig::scalar(vec![Type::String], Type::Bool));
+
+    assert!(analyze(&query).is_err());
+    assert!(analyze_with_options(&query, &options).is_ok());
+}
+
+#[test]
+fn test_aggregate_in_where_clause() {
+    let tokens = tokenize("FROM e IN events WHERE COUNT() > 1 PROJECT INTO e").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UnexpectedAggregate(1, 24, name)) if name == "COUNT"
+    ));
+}
+
+#[test]
+fn test_nested_aggregate() {
+    let tokens =
+        tokenize("FROM e IN events PROJECT INTO { total: SUM(SUM(e.data.total)) }").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UnexpectedAggregate(1, 44, name)) if name == "SUM"
+    ));
+}
+
+#[test]
+fn test_group_by_project_grouped_and_aggregates() {
+    let tokens = tokenize(
+        "FROM e IN events GROUP BY e.type HAVING COUNT() > 2 ORDER BY e.type ASC PROJECT INTO { type: e.type, total: SUM(e.data.total) }",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(analyze(&query).is_ok());
+}
+
+#[test]
+fn test_group_by_project_ungrouped_field() {
+    let tokens =
+        tokenize("FROM e IN events GROUP BY e.type PROJECT INTO { id: e.id, total: COUNT() }")
+            .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UngroupedExpr(1, 53, name)) if name == "e"
+    ));
+}
+
+#[test]
+fn test_group_by_having_ungrouped_field() {
+    let tokens =
+        tokenize("FROM e IN events GROUP BY e.type HAVING e.id == \"1\" PROJECT INTO e.type")
+            .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UngroupedExpr(1, 41, name)) if name == "e"
+    ));
+}
+
+#[test]
+fn test_implicit_group_project_ungrouped_field() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO { total: COUNT(), id: e.id }").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UngroupedExpr(1, 53, name)) if name == "e"
+    ));
+}
+
+#[test]
+fn test_infer_from_events_with_group_by_alias() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_with_group_by_alias.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(analyze(&query).unwrap());
+}
+
+#[test]
+fn test_group_by_implicit_binding() {
+    let tokens = tokenize(
+        "FROM e IN events GROUP BY e.type HAVING type != \"foo\" PROJECT INTO { type: type, total: COUNT() }",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(analyze(&query).is_ok());
+}
+
+#[test]
+fn test_group_by_binding_not_visible_in_where() {
+    let tokens =
+        tokenize("FROM e IN events WHERE type != \"foo\" GROUP BY e.type PROJECT INTO type")
+            .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::UnknownVariable(1, 24, name)) if name == "type"
+    ));
+}
+
+#[test]
+fn test_group_by_binding_shadowing_source() {
+    let tokens = tokenize("FROM e IN events GROUP BY e.data.e PROJECT INTO COUNT()").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(query.group_by.as_ref().unwrap().binding.is_none());
+    assert!(analyze(&query).is_ok());
+}
+
+#[test]
+fn test_group_by_explicit_binding_shadowing_source() {
+    let tokens = tokenize("FROM e IN events GROUP BY e.data.x AS e PROJECT INTO e").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        analyze(&query),
+        Err(AnalysisError::DuplicateBinding(1, 27, name)) if name == "e"
+    ));
+}
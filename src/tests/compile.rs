@@ -0,0 +1,86 @@
+use crate::Query;
+use crate::compile::{compile_predicate, compile_projection};
+use crate::error::EvalError;
+use crate::eval::eval;
+use crate::lexer::tokenize;
+use crate::parser::parse;
+use serde_json::{Value as JsonValue, json};
+
+fn events() -> Vec<JsonValue> {
+    serde_json::from_str(include_str!("./resources/events.json")).unwrap()
+}
+
+fn query(source: &str) -> Query {
+    let tokens = tokenize(source).unwrap();
+    parse(tokens.as_slice()).unwrap()
+}
+
+#[test]
+fn test_compile_matches_eval() {
+    let query = query(
+        r#"FROM e IN events
+           WHERE (e.data.price >= 20 OR e.type != "io.eventsourcingdb.library.book-acquired") AND NOT e.data.missing == 1
+           PROJECT INTO { id: e.id, type: LOWER(e.type), discounted: -e.data.price * 2 / 4, tags: [e.subject, e.data.category] }"#,
+    );
+    let predicate = compile_predicate(query.predicate.as_ref().unwrap()).unwrap();
+    let projection = compile_projection(&query.projection).unwrap();
+
+    let mut results = Vec::new();
+    for event in events() {
+        if predicate(&event).unwrap() {
+            results.push(projection(&event).unwrap());
+        }
+    }
+
+    assert_eq!(results, eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_compile_access_on_missing_and_non_record_values() {
+    let query = query("FROM e IN events WHERE e.data.price.amount > 1 PROJECT INTO e.data.a.b");
+    let predicate = compile_predicate(query.predicate.as_ref().unwrap()).unwrap();
+    let projection = compile_projection(&query.projection).unwrap();
+
+    assert!(!predicate(&json!({ "data": {} })).unwrap());
+    assert!(matches!(
+        predicate(&json!({ "data": { "price": 3 } })),
+        Err(EvalError::TypeMismatch(1, 24, "record", "number"))
+    ));
+    assert_eq!(
+        projection(&json!({ "data": null })).unwrap(),
+        JsonValue::Null
+    );
+}
+
+#[test]
+fn test_compile_rejects_second_variable() {
+    let query = query("FROM e IN events FROM f IN events WHERE e.id == f.id PROJECT INTO e");
+
+    assert!(matches!(
+        compile_predicate(query.predicate.as_ref().unwrap()),
+        Err(EvalError::UnknownVariable(1, 49, name)) if name == "f"
+    ));
+}
+
+#[test]
+fn test_compile_rejects_aggregate() {
+    let query = query("FROM e IN events PROJECT INTO { total: SUM(e.data.price) }");
+
+    assert!(matches!(
+        compile_projection(&query.projection),
+        Err(EvalError::UnexpectedAggregate(1, 40, name)) if name == "SUM"
+    ));
+}
+
+#[test]
+fn test_compile_quoted_identifiers() {
+    let query = query(
+        "FROM e IN events WHERE e.data.`content-type` == \"application/json\" PROJECT INTO e.data.order_id",
+    );
+    let predicate = compile_predicate(query.predicate.as_ref().unwrap()).unwrap();
+    let projection = compile_projection(&query.projection).unwrap();
+    let event = json!({ "data": { "content-type": "application/json", "order_id": 42 } });
+
+    assert!(predicate(&event).unwrap());
+    assert_eq!(projection(&event).unwrap(), json!(42));
+}
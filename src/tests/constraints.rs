@@ -0,0 +1,105 @@
+use crate::constraints::{Constraints, TimeRange, extract_constraints};
+use crate::lexer::tokenize;
+use crate::parser::parse;
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
+fn extract(src: &str) -> Constraints {
+    let tokens = tokenize(src).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    extract_constraints(query.predicate.as_ref().unwrap(), "e")
+}
+
+fn residual(constraints: &Constraints) -> Option<String> {
+    constraints.residual.as_ref().map(|expr| expr.to_string())
+}
+
+#[test]
+fn test_extract_constraints_from_events_with_index_constraints() {
+    let constraints = extract(include_str!(
+        "./resources/from_events_with_index_constraints.eql"
+    ));
+
+    assert_eq!(
+        constraints.types,
+        Some(BTreeSet::from([
+            "book-acquired".to_string(),
+            "book-borrowed".to_string()
+        ]))
+    );
+    assert_eq!(constraints.subject_prefix.as_deref(), Some("/books"));
+    assert_eq!(
+        constraints.time,
+        TimeRange {
+            start: Bound::Included("2024-01-01T00:00:00Z".to_string()),
+            end: Bound::Excluded("2025-01-01T00:00:00Z".to_string()),
+        }
+    );
+    assert_eq!(residual(&constraints).as_deref(), Some("e.data.price > 20"));
+}
+
+#[test]
+fn test_extract_constraints_with_lowercase_startswith() {
+    let constraints =
+        extract("FROM e IN events WHERE startswith(e.subject, \"/books\") PROJECT INTO e");
+
+    assert_eq!(constraints.subject_prefix.as_deref(), Some("/books"));
+    assert_eq!(residual(&constraints), None);
+}
+
+#[test]
+fn test_extract_constraints_intersects_conditions() {
+    let constraints = extract(
+        "FROM e IN events
+         WHERE (e.type == \"a\" OR e.type == \"b\") AND e.type == \"b\"
+         AND STARTSWITH(e.subject, \"/books\") AND STARTSWITH(e.subject, \"/books/1\")
+         AND e.time > \"2024\" AND e.time >= \"2024\" AND e.time <= \"2026\" AND e.time == \"2025\"
+         PROJECT INTO e",
+    );
+
+    assert_eq!(constraints.types, Some(BTreeSet::from(["b".to_string()])));
+    assert_eq!(constraints.subject_prefix.as_deref(), Some("/books/1"));
+    assert_eq!(
+        constraints.time,
+        TimeRange {
+            start: Bound::Included("2025".to_string()),
+            end: Bound::Included("2025".to_string()),
+        }
+    );
+    assert!(constraints.residual.is_none());
+}
+
+#[test]
+fn test_extract_constraints_of_contradicting_types() {
+    let constraints =
+        extract("FROM e IN events WHERE e.type == \"a\" AND e.type == \"b\" PROJECT INTO e");
+
+    assert_eq!(constraints.types, Some(BTreeSet::new()));
+    assert!(constraints.residual.is_none());
+}
+
+#[test]
+fn test_extract_constraints_keeps_other_conditions_as_residual() {
+    let constraints = extract(
+        "FROM e IN events FROM o IN events
+         WHERE (e.type == \"a\" OR e.id == \"1\")
+         AND e.type != \"b\"
+         AND o.type == \"c\"
+         AND e.time > o.time
+         AND STARTSWITH(e.subject, \"/books\") AND STARTSWITH(e.subject, \"/authors\")
+         AND NOT e.time < \"2024\"
+         PROJECT INTO e",
+    );
+
+    assert!(constraints.types.is_none());
+    assert_eq!(constraints.subject_prefix.as_deref(), Some("/books"));
+    assert!(constraints.time.is_unbounded());
+    assert_eq!(
+        residual(&constraints).as_deref(),
+        Some(
+            "(e.type == \"a\" OR e.id == \"1\") AND e.type != \"b\" AND o.type == \"c\" \
+             AND e.time > o.time AND STARTSWITH(e.subject, \"/authors\") AND NOT e.time < \"2024\""
+        )
+    );
+}
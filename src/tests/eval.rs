@@ -0,0 +1,269 @@
+use crate::Type;
+use crate::analysis::{AnalysisOptions, analyze_with_options};
+use crate::error::{Error, EvalError};
+use crate::eval::{EvalResult, EventStore, eval, eval_iter, eval_plan_iter};
+use crate::functions::FunctionSig;
+use crate::lexer::tokenize;
+use crate::parser::parse;
+use crate::plan::{Plan, lower};
+use serde_json::{Value as JsonValue, json};
+use std::cell::Cell;
+
+fn events() -> Vec<JsonValue> {
+    serde_json::from_str(include_str!("./resources/events.json")).unwrap()
+}
+
+struct CountingStore {
+    events: Vec<JsonValue>,
+    scanned: Cell<usize>,
+}
+
+impl EventStore for CountingStore {
+    fn scan(&self, _source: &str) -> EvalResult<Box<dyn Iterator<Item = JsonValue> + '_>> {
+        Ok(Box::new(self.events.iter().cloned().inspect(|_| {
+            self.scanned.set(self.scanned.get() + 1);
+        })))
+    }
+}
+
+#[test]
+fn test_eval_from_events_nested_data() {
+    let tokens = tokenize(include_str!("./resources/from_events_nested_data.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_from_events_using_subquery() {
+    let tokens = tokenize(include_str!("./resources/from_events_using_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_from_events_with_top_identity_projection() {
+    let tokens = tokenize("FROM e IN events ORDER BY e.time DESC TOP 2 PROJECT INTO e.id").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_from_events_where_subject_project_record_with_count() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_where_subject_project_record_with_count.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_from_events_with_group_by_alias() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_with_group_by_alias.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_group_by_with_aggregates() {
+    let tokens = tokenize(
+        "FROM e IN events \
+         WHERE e.type == \"io.eventsourcingdb.library.book-acquired\" \
+         GROUP BY e.data.category HAVING COUNT() > 1 \
+         ORDER BY category ASC \
+         PROJECT INTO { category: category, total: SUM(e.data.total), avg: AVG(e.data.price), max: MAX(e.data.price) }",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_from_events_with_distinct() {
+    let tokens = tokenize(
+        "FROM e IN events WHERE e.data.price > 20 PROJECT INTO DISTINCT { price: e.data.price }",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_groups_and_distinct_mixed_integers_and_floats() {
+    let events = vec![
+        json!({ "data": { "a": 1, "b": [2, { "c": 3 }] } }),
+        json!({ "data": { "a": 1.0, "b": [2.0, { "c": 3.0 }] } }),
+    ];
+
+    let tokens =
+        tokenize("FROM e IN events GROUP BY e.data.a PROJECT INTO { a: a, n: COUNT() }").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert_eq!(
+        eval(&query, events.as_slice()).unwrap(),
+        vec![json!({ "a": 1, "n": 2 })]
+    );
+
+    let tokens = tokenize("FROM e IN events PROJECT INTO DISTINCT [e.data.a, e.data.b]").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert_eq!(eval(&query, events.as_slice()).unwrap().len(), 1);
+}
+
+#[test]
+fn test_eval_subject_source_with_skip() {
+    let tokens = tokenize(
+        "FROM e IN \"/books/42\" SKIP 1 PROJECT INTO { id: e.id, type: UPPER(e.type), day: DAY(e.time) }",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_division_by_zero() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO e.data.price / 0").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        eval(&query, events().as_slice()),
+        Err(EvalError::DivisionByZero(1, 31))
+    ));
+}
+
+#[test]
+fn test_eval_error_into_error() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO e.data.price / 0").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+    let err: Error = eval(&query, events().as_slice()).unwrap_err().into();
+
+    assert!(matches!(err, Error::Eval(EvalError::DivisionByZero(1, 31))));
+}
+
+#[test]
+fn test_eval_modulo_by_zero() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO e.data.price % 0").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        eval(&query, events().as_slice()),
+        Err(EvalError::DivisionByZero(1, 31))
+    ));
+}
+
+#[test]
+fn test_eval_power_and_modulo() {
+    let tokens = tokenize(
+        "FROM e IN events TOP 1 PROJECT INTO [2 ^ 3 ^ 2, -2 ^ 2, (-2) ^ 2, 2 ^ -1, 7 % 3, -7 % 3, 7.5 % 2, e.data.price % 1]",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(eval(&query, events().as_slice()).unwrap());
+}
+
+#[test]
+fn test_eval_unknown_source() {
+    let tokens = tokenize("FROM e IN books PROJECT INTO e").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        eval(&query, events().as_slice()),
+        Err(EvalError::UnknownSource(name)) if name == "books"
+    ));
+}
+
+#[test]
+fn test_eval_rand() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO RAND()").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    fastrand::seed(42);
+    let results = eval(&query, events().as_slice()).unwrap();
+
+    assert!(results.iter().all(|value| {
+        value
+            .as_f64()
+            .is_some_and(|value| (0.0..1.0).contains(&value))
+    }));
+
+    fastrand::seed(42);
+    assert_eq!(eval(&query, events().as_slice()).unwrap(), results);
+}
+
+#[test]
+fn test_eval_custom_function() {
+    let mut options = AnalysisOptions::default();
+    options.functions.register(
+        "SLUGIFY",
+        FunctionSig::scalar(vec![Type::String], Type::String),
+    );
+
+    let tokens = tokenize("FROM e IN events PROJECT INTO SLUGIFY(e.subject)").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(analyze_with_options(&query, &options).is_ok());
+    assert!(matches!(
+        eval(&query, events().as_slice()),
+        Err(EvalError::UnknownFunction(1, 31, name)) if name == "SLUGIFY"
+    ));
+}
+
+#[test]
+fn test_eval_iter_stops_scanning_after_limit() {
+    let store = CountingStore {
+        events: events(),
+        scanned: Cell::new(0),
+    };
+    let tokens = tokenize(r#"FROM e IN events WHERE e.type == "io.eventsourcingdb.library.book-acquired" TOP 1 PROJECT INTO e.id"#).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+    let results = eval_iter(&query, &store)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(results.len(), 1);
+    assert!(store.scanned.get() < store.events.len());
+}
+
+#[test]
+fn test_eval_plan_without_projection() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO e").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+    let Plan::Project { input, .. } = lower(&query) else {
+        unreachable!()
+    };
+
+    assert!(matches!(
+        eval_plan_iter(&input, events().as_slice()).next(),
+        Some(Err(EvalError::InvalidPlan("Scan")))
+    ));
+}
+
+#[test]
+fn test_eval_optimized_query_matches_plain_plan() {
+    let tokens = tokenize(
+        "FROM o IN (FROM e IN events WHERE e.type == \"io.eventsourcingdb.library.book-acquired\" PROJECT INTO { id: e.id, price: e.data.price, subject: e.subject })
+         FROM p IN (FROM e IN events ORDER BY e.time DESC PROJECT INTO { id: e.id, subject: e.subject })
+         WHERE o.price > 10 AND o.subject == p.subject AND p.id != \"1\"
+         PROJECT INTO [o.id, p.id]",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+    let plain = eval_plan_iter(&lower(&query), events().as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert!(!plain.is_empty());
+    assert_eq!(eval(&query, events().as_slice()).unwrap(), plain);
+}
@@ -0,0 +1,70 @@
+use crate::explain::{explain, explain_optimized};
+use crate::lexer::tokenize;
+use crate::parser::parse;
+
+const PUSHED_DOWN: &str = "FROM o IN (FROM e IN events ORDER BY e.time DESC PROJECT INTO { id: e.id, value: e.data.total })
+                           WHERE o.value > 100
+                           TOP 3
+                           PROJECT INTO o.id";
+
+#[test]
+fn test_explain_from_events_using_subquery() {
+    let tokens = tokenize(include_str!("./resources/from_events_using_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(explain(&query).to_string());
+}
+
+#[test]
+fn test_explain_from_events_with_group_by_and_having() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_with_group_by_and_having.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(explain(&query).to_string());
+}
+
+#[test]
+fn test_explain_serialized_from_events_using_subquery() {
+    let tokens = tokenize(include_str!("./resources/from_events_using_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(explain(&query));
+}
+
+#[test]
+fn test_explain_optimized_from_events_using_subquery() {
+    let tokens = tokenize(include_str!("./resources/from_events_using_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(explain_optimized(&query).to_string());
+}
+
+#[test]
+fn test_explain_pushed_down_filter() {
+    let tokens = tokenize(PUSHED_DOWN).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(explain(&query).to_string());
+
+    let tokens = tokenize(
+        "FROM o IN (FROM e IN events PROJECT INTO { v: e.data.x }) WHERE o.v PROJECT INTO o",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(
+        "explain_pushed_down_bare_filter",
+        explain(&query).to_string()
+    );
+}
+
+#[test]
+fn test_explain_serialized_pushed_down_filter() {
+    let tokens = tokenize(PUSHED_DOWN).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(explain(&query));
+}
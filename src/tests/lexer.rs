@@ -1,4 +1,6 @@
-use crate::lexer::tokenize;
+use crate::error::LexerError;
+use crate::lexer::{tokenize, tokenize_with_comments};
+use crate::token::{Operator, Sym, Symbol};
 
 #[test]
 fn test_lexer_all_kind() {
@@ -9,3 +11,139 @@ fn test_lexer_all_kind() {
 fn test_lexer_negative_number() {
     insta::assert_yaml_snapshot!(tokenize("-123.456").unwrap());
 }
+
+#[test]
+fn test_lexer_subtraction_without_spaces() {
+    insta::assert_yaml_snapshot!(tokenize("e.a-1").unwrap());
+}
+
+#[test]
+fn test_lexer_number_with_exponent() {
+    insta::assert_yaml_snapshot!(tokenize("1.5e3 2E-2 1.foo").unwrap());
+}
+
+#[test]
+fn test_lexer_skips_comments() {
+    insta::assert_yaml_snapshot!(
+        tokenize(include_str!("./resources/from_events_with_comments.eql")).unwrap()
+    );
+}
+
+#[test]
+fn test_lexer_keeps_comments() {
+    insta::assert_yaml_snapshot!(
+        tokenize_with_comments(include_str!("./resources/from_events_with_comments.eql")).unwrap()
+    );
+}
+
+#[test]
+fn test_lexer_double_minus_without_space() {
+    let tokens = tokenize("e.a--4 -- comment\n--\ne.b").unwrap();
+    let syms = tokens.iter().map(|token| token.sym).collect::<Vec<_>>();
+
+    assert!(matches!(
+        syms.as_slice(),
+        [
+            Sym::Id("e"),
+            Sym::Symbol(Symbol::Dot),
+            Sym::Id("a"),
+            Sym::Operator(Operator::Sub),
+            Sym::Operator(Operator::Sub),
+            Sym::Number(4.0),
+            Sym::Id("e"),
+            Sym::Symbol(Symbol::Dot),
+            Sym::Id("b"),
+            Sym::Eof,
+        ]
+    ));
+}
+
+#[test]
+fn test_lexer_unterminated_block_comment() {
+    assert!(matches!(
+        tokenize("FROM e IN events\n  /* PROJECT INTO e"),
+        Err(LexerError::UnterminatedComment(2, 3))
+    ));
+}
+
+#[test]
+fn test_lexer_strings() {
+    insta::assert_yaml_snapshot!(
+        tokenize(r#""say \"hi\"" 'it\'s' "a\\b\n\t\u{1F600}" '"' """#).unwrap()
+    );
+}
+
+#[test]
+fn test_lexer_invalid_escape() {
+    assert!(matches!(
+        tokenize(r#"e.a == "foo\qbar""#),
+        Err(LexerError::InvalidEscape(1, 12))
+    ));
+    assert!(matches!(
+        tokenize(r#""\u{110000}""#),
+        Err(LexerError::InvalidEscape(1, 2))
+    ));
+    assert!(matches!(
+        tokenize(r#"'\u{}'"#),
+        Err(LexerError::InvalidEscape(1, 2))
+    ));
+    assert!(matches!(
+        tokenize(r#"'\u{+41}'"#),
+        Err(LexerError::InvalidEscape(1, 2))
+    ));
+}
+
+#[test]
+fn test_lexer_unterminated_string() {
+    assert!(matches!(
+        tokenize("FROM e IN events\nWHERE e.a == \"foo PROJECT INTO e"),
+        Err(LexerError::UnterminatedString(2, 14))
+    ));
+    assert!(matches!(
+        tokenize(r#"'it\'s"#),
+        Err(LexerError::UnterminatedString(1, 1))
+    ));
+}
+
+#[test]
+fn test_lexer_invalid_number() {
+    assert!(matches!(
+        tokenize("TOP 12abc"),
+        Err(LexerError::InvalidNumber(1, 5))
+    ));
+    assert!(matches!(
+        tokenize("e.a > 1e"),
+        Err(LexerError::InvalidNumber(1, 7))
+    ));
+    assert!(matches!(
+        tokenize("1e999"),
+        Err(LexerError::InvalidNumber(1, 1))
+    ));
+}
+
+#[test]
+fn test_lexer_unexpected_character() {
+    assert!(matches!(
+        tokenize("e.a # 1"),
+        Err(LexerError::UnexpectedCharacter(1, 5, '#'))
+    ));
+    assert!(matches!(
+        tokenize("e.a\n  ! e.b"),
+        Err(LexerError::UnexpectedCharacter(2, 3, '!'))
+    ));
+}
+
+#[test]
+fn test_lexer_identifiers() {
+    insta::assert_yaml_snapshot!(
+        tokenize("order_id _private prix_unité 日付 `content-type` `and` ``").unwrap()
+    );
+}
+
+#[test]
+fn test_lexer_unterminated_quoted_identifier() {
+    assert!(matches!(
+        tokenize("e.`content-type\n== 1"),
+        Err(LexerError::UnterminatedIdentifier(1, 3))
+    ));
+}
@@ -1,2 +1,11 @@
+mod analysis;
+#[cfg(feature = "eval")]
+mod compile;
+mod constraints;
+#[cfg(feature = "eval")]
+mod eval;
+mod explain;
 mod lexer;
+mod optimize;
 mod parser;
+mod plan;
@@ -0,0 +1,219 @@
+use crate::Value;
+use crate::analysis::analyze;
+use crate::lexer::tokenize;
+use crate::optimize::{
+    flatten_subqueries, optimize, push_down_predicates, simplify, simplify_predicate,
+};
+use crate::parser::parse;
+use crate::plan::lower;
+
+fn push_down(source: &str) -> String {
+    let tokens = tokenize(source).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    lower(&push_down_predicates(&query)).to_string()
+}
+
+fn flatten(source: &str) -> String {
+    let tokens = tokenize(source).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    lower(&flatten_subqueries(&query)).to_string()
+}
+
+#[test]
+fn test_push_down_from_events_using_subquery() {
+    insta::assert_snapshot!(push_down(include_str!(
+        "./resources/from_events_using_subquery.eql"
+    )));
+}
+
+#[test]
+fn test_push_down_keeps_conjuncts_depending_on_other_sources() {
+    insta::assert_snapshot!(push_down(
+        "FROM o IN (FROM e IN events WHERE e.price > 1 OR e.free PROJECT INTO { id: e.id, total: e.price * e.qty })
+         FROM e IN events
+         WHERE (o.total > 100 AND o.id == e.id) AND NOT (o.id == \"42\")
+         PROJECT INTO o"
+    ));
+}
+
+#[test]
+fn test_push_down_through_nested_subqueries() {
+    insta::assert_snapshot!(push_down(
+        "FROM a IN (FROM b IN (FROM e IN events PROJECT INTO { v: e.data.value }) PROJECT INTO { w: b.v })
+         WHERE a.w == 1
+         PROJECT INTO a"
+    ));
+}
+
+#[test]
+fn test_push_down_skips_limited_subquery() {
+    insta::assert_snapshot!(push_down(
+        "FROM o IN (FROM e IN events TOP 10 PROJECT INTO { id: e.id }) WHERE o.id == \"1\" PROJECT INTO o"
+    ));
+}
+
+#[test]
+fn test_push_down_skips_subquery_projecting_function_calls() {
+    insta::assert_snapshot!(push_down(
+        "FROM o IN (FROM e IN events PROJECT INTO { id: UPPER(e.id) }) WHERE o.id == \"A\" PROJECT INTO o"
+    ));
+}
+
+#[test]
+fn test_push_down_whole_record_usage() {
+    insta::assert_snapshot!(push_down(
+        "FROM o IN (FROM e IN events PROJECT INTO { id: e.id }) WHERE o == { id: \"1\" } PROJECT INTO o"
+    ));
+}
+
+#[test]
+fn test_flatten_from_events_using_subquery() {
+    insta::assert_snapshot!(flatten(include_str!(
+        "./resources/from_events_using_subquery.eql"
+    )));
+}
+
+#[test]
+fn test_flatten_composes_every_clause() {
+    insta::assert_snapshot!(flatten(
+        "FROM b IN \"/books\"
+         FROM o IN (FROM e IN events FROM r IN \"/readers\" WHERE e.subject == r.subject PROJECT INTO { book: e.data.book, price: e.data.price + 1 })
+         WHERE o.book == b.id
+         GROUP BY o.price AS price HAVING price > 10
+         ORDER BY price DESC
+         PROJECT INTO { price: price, books: COUNT(), whole: o }"
+    ));
+}
+
+#[test]
+fn test_flatten_nested_subqueries_projecting_variables() {
+    insta::assert_snapshot!(flatten(
+        "FROM a IN (FROM b IN (FROM e IN events PROJECT INTO e) WHERE b.type == \"x\" PROJECT INTO -b.data.n)
+         PROJECT INTO a * 2"
+    ));
+}
+
+#[test]
+fn test_flatten_skips_ordered_and_distinct_subqueries() {
+    insta::assert_snapshot!(flatten(
+        "FROM o IN (FROM e IN events ORDER BY e.time ASC PROJECT INTO { id: e.id })
+         FROM p IN (FROM e IN events PROJECT INTO DISTINCT { id: e.id })
+         PROJECT INTO [o.id, p.id]"
+    ));
+}
+
+#[test]
+fn test_flatten_skips_clashing_variables_and_missing_fields() {
+    insta::assert_snapshot!(flatten(
+        "FROM e IN events
+         FROM o IN (FROM e IN events PROJECT INTO { id: e.id })
+         FROM p IN (FROM f IN events PROJECT INTO { id: f.id })
+         PROJECT INTO [o.id, p.name]"
+    ));
+}
+
+#[test]
+fn test_simplify_parser_unhinged_unary_op() {
+    let tokens = tokenize(include_str!("./resources/parser_unhinged_unary_op.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_yaml_snapshot!(simplify_predicate(query.predicate.as_ref().unwrap()));
+}
+
+#[test]
+fn test_simplify_values() {
+    let tokens = tokenize(
+        "FROM e IN events PROJECT INTO [
+            1 + 2 * 3,
+            -(1 + 2) * -(-4),
+            (e.a),
+            ((e.a + 1)) * 2,
+            (e.a + 0) + e.b,
+            1 / 0,
+            7 % 0,
+            2 ^ 10 % 5,
+            (-2) ^ e.n,
+            (1 - 3) ^ e.n,
+            (-e.n) ^ 2,
+            (-1) ^ 0.5,
+            1e308 * 10,
+            1e308 + 1e308,
+            -1e308 - 1e308,
+            \"a\" < \"b\",
+            1 == \"1\",
+            true < false,
+            true XOR false,
+            NOT (NOT (e.a OR e.b)),
+            -(-(e.n)),
+            true AND e.x,
+            e.x AND true,
+            true AND (e.a == 1),
+            false OR (e.a OR e.b),
+            (true AND (e.a OR e.b)) AND e.c,
+            false AND e.x,
+            e.x OR true,
+            { total: (e.price * (2 - 1)) }
+        ]",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+    let Value::Array(elems) = simplify(&query.projection).value else {
+        unreachable!()
+    };
+
+    insta::assert_snapshot!(
+        elems
+            .iter()
+            .map(|elem| elem.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}
+
+#[test]
+fn test_simplify_predicates() {
+    let tokens = tokenize(
+        "FROM e IN events WHERE (true AND e.x) AND (e.y OR false) AND NOT (1 > 2) PROJECT INTO e",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(simplify_predicate(query.predicate.as_ref().unwrap()).to_string());
+}
+
+#[test]
+fn test_optimize_drops_satisfied_clauses() {
+    let tokens = tokenize(
+        "FROM o IN (FROM e IN events WHERE 1 < 2 ORDER BY e.time ASC PROJECT INTO { n: 2 * 3 })
+         WHERE o.n > 5 OR false
+         GROUP BY o.n HAVING true
+         PROJECT INTO n + 1",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(lower(&optimize(&query)).to_string());
+}
+
+#[test]
+fn test_optimize_keeps_queries_analyzable() {
+    for source in [
+        "FROM o IN (FROM e IN events PROJECT INTO { v: e.data.x + 1 }) PROJECT INTO o.v * 2",
+        "FROM o IN (FROM e IN events PROJECT INTO { v: e.data.x + 1 }) WHERE o.v > 1 PROJECT INTO o.v * 2",
+        "FROM o IN (FROM e IN events ORDER BY e.time DESC PROJECT INTO { v: e.data.x }) WHERE o.v PROJECT INTO o",
+        "FROM o IN (FROM e IN events WHERE e.type == \"order\" PROJECT INTO { v: e.data.x, t: e.time })
+         WHERE o.v > 1
+         GROUP BY o.t HAVING COUNT() > 1
+         ORDER BY t ASC
+         PROJECT INTO { t: t, total: SUM(o.v) }",
+        "FROM o IN (FROM e IN events ORDER BY e.time DESC PROJECT INTO { v: e.data.x + 1 }) WHERE o.v > 1 PROJECT INTO o.v * 2",
+    ] {
+        let tokens = tokenize(source).unwrap();
+        let query = parse(tokens.as_slice()).unwrap();
+
+        assert!(analyze(&query).is_ok(), "{source}");
+        assert!(analyze(&optimize(&query)).is_ok(), "{source}");
+    }
+}
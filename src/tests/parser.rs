@@ -1,5 +1,6 @@
-use crate::lexer::tokenize;
+use crate::lexer::{tokenize, tokenize_with_comments};
 use crate::parser::parse;
+use crate::{SourceKind, Value};
 
 #[test]
 fn test_parse_from_events_nested_data() {
@@ -46,6 +47,105 @@ fn test_parse_binary_op() {
     insta::assert_yaml_snapshot!(parse(tokens.as_slice()).unwrap());
 }
 
+#[test]
+fn test_parse_logical_op_precedence() {
+    let tokens = tokenize(include_str!("./resources/parser_logical_op_precedence.eql")).unwrap();
+    insta::assert_yaml_snapshot!(parse(tokens.as_slice()).unwrap());
+}
+
+#[test]
+fn test_parse_unary_op_precedence() {
+    let tokens = tokenize(include_str!("./resources/parser_unary_op_precedence.eql")).unwrap();
+    insta::assert_yaml_snapshot!(parse(tokens.as_slice()).unwrap());
+}
+
+#[test]
+fn test_parse_subtraction_and_negative_numbers() {
+    let tokens = tokenize(include_str!(
+        "./resources/parser_subtraction_and_negative_numbers.eql"
+    ))
+    .unwrap();
+    insta::assert_yaml_snapshot!(parse(tokens.as_slice()).unwrap());
+}
+
+#[test]
+fn test_parse_power_and_modulo() {
+    let tokens = tokenize(include_str!("./resources/parser_power_and_modulo.eql")).unwrap();
+    insta::assert_yaml_snapshot!(parse(tokens.as_slice()).unwrap());
+}
+
+#[test]
+fn test_parse_ignores_kept_comments() {
+    let src = include_str!("./resources/from_events_with_comments.eql");
+    let with_comments = tokenize_with_comments(src).unwrap();
+    let without_comments = tokenize(src).unwrap();
+
+    assert_eq!(
+        format!("{:?}", parse(with_comments.as_slice()).unwrap()),
+        format!("{:?}", parse(without_comments.as_slice()).unwrap()),
+    );
+}
+
+#[test]
+fn test_display_double_negation_is_not_a_comment() {
+    let tokens = tokenize("FROM e IN events PROJECT INTO [- -e.a, -(-1), 1 - -1]").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert_eq!(query.projection.to_string(), "[- -e.a, -(-1), 1 - -1]");
+}
+
+#[test]
+fn test_parse_string_escapes() {
+    let tokens = tokenize(
+        r#"FROM e IN 'books/\'rare\'' PROJECT INTO ["say \"hi\"", 'a\\b', "\n\t\u{e9}", '"']"#,
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert!(matches!(
+        &query.sources[0].kind,
+        SourceKind::Subject(subject) if subject == "books/'rare'"
+    ));
+
+    let Value::Array(elems) = &query.projection.value else {
+        unreachable!()
+    };
+    let values = elems
+        .iter()
+        .map(|elem| match &elem.value {
+            Value::String(s) => s.as_str(),
+            _ => unreachable!(),
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(values, ["say \"hi\"", "a\\b", "\n\té", "\""]);
+    assert_eq!(
+        query.projection.to_string(),
+        r#"["say \"hi\"", "a\\b", "\n\té", "\""]"#
+    );
+}
+
+#[test]
+fn test_parse_identifiers() {
+    let tokens = tokenize(include_str!("./resources/parser_identifiers.eql")).unwrap();
+    insta::assert_yaml_snapshot!(parse(tokens.as_slice()).unwrap());
+}
+
+#[test]
+fn test_display_quotes_identifiers_when_needed() {
+    let tokens = tokenize(include_str!("./resources/parser_identifiers.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    assert_eq!(
+        query.predicate.unwrap().to_string(),
+        "_e.data.order_id > 10 AND _e.data.`content-type` == \"json\" AND _e.data.prix_unité < 5"
+    );
+    assert_eq!(
+        query.projection.to_string(),
+        "{`content-type`: _e.data.`content-type`, `and`: _e.data.`and`, données: _e.data.données}"
+    );
+}
+
 #[test]
 fn test_parser_unhinged_unary_op() {
     let tokens = tokenize(include_str!("./resources/parser_unhinged_unary_op.eql")).unwrap();
@@ -66,3 +166,12 @@ fn test_parser_from_events_with_distinct() {
     let tokens = tokenize(include_str!("./resources/from_events_with_distinct.eql")).unwrap();
     insta::assert_yaml_snapshot!(parse(tokens.as_slice()).unwrap());
 }
+
+#[test]
+fn test_parser_from_events_with_group_by_alias() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_with_group_by_alias.eql"
+    ))
+    .unwrap();
+    insta::assert_yaml_snapshot!(parse(tokens.as_slice()).unwrap());
+}
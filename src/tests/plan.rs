@@ -0,0 +1,68 @@
+use crate::lexer::tokenize;
+use crate::parser::parse;
+use crate::plan::lower;
+
+#[test]
+fn test_plan_from_events_nested_data() {
+    let tokens = tokenize(include_str!("./resources/from_events_nested_data.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(lower(&query).to_string());
+}
+
+#[test]
+fn test_plan_from_events_using_subquery() {
+    let tokens = tokenize(include_str!("./resources/from_events_using_subquery.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(lower(&query).to_string());
+}
+
+#[test]
+fn test_plan_from_events_with_group_by_and_having() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_with_group_by_and_having.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(lower(&query).to_string());
+}
+
+#[test]
+fn test_plan_from_events_with_distinct() {
+    let tokens = tokenize(include_str!("./resources/from_events_with_distinct.eql")).unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(lower(&query).to_string());
+}
+
+#[test]
+fn test_plan_from_events_where_subject_project_record_with_count() {
+    let tokens = tokenize(include_str!(
+        "./resources/from_events_where_subject_project_record_with_count.eql"
+    ))
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(lower(&query).to_string());
+}
+
+#[test]
+fn test_plan_cross_join_and_sort() {
+    let tokens = tokenize(
+        "FROM e IN events FROM b IN \"/books\" WHERE e.subject == b.subject ORDER BY e.time DESC SKIP 2 PROJECT INTO [e.id, b.id]",
+    )
+    .unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(lower(&query).to_string());
+}
+
+#[test]
+fn test_plan_without_source() {
+    let tokens = tokenize("PROJECT INTO -(1 + 2) * 3").unwrap();
+    let query = parse(tokens.as_slice()).unwrap();
+
+    insta::assert_snapshot!(lower(&query).to_string());
+}
@@ -23,7 +23,10 @@ use std::fmt::{Display, Formatter};
 pub enum Sym<'a> {
     /// Identifier (variable names, keywords not yet classified)
     Id(&'a str),
-    /// String literal
+    /// Identifier quoted with backticks, without them (e.g., `content-type`).
+    /// Unlike [`Sym::Id`], it is never a keyword
+    QuotedId(&'a str),
+    /// String literal as written, quotes and escape sequences included
     String(&'a str),
     /// Numeric literal
     Number(f64),
@@ -33,6 +36,9 @@ pub enum Sym<'a> {
     Operator(Operator),
     /// Structural symbol (parentheses, brackets, etc.)
     Symbol(Symbol),
+    /// Comment, including its delimiters (e.g., `-- recent orders`), only
+    /// produced by [`tokenize_with_comments`](crate::prelude::tokenize_with_comments)
+    Comment(&'a str),
     /// End of file marker
     Eof,
 }
@@ -41,11 +47,13 @@ impl Display for Sym<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Sym::Id(id) => write!(f, "{}", id),
-            Sym::String(s) => write!(f, "\"{}\"", s),
+            Sym::QuotedId(id) => write!(f, "`{}`", id),
+            Sym::String(s) => write!(f, "{}", s),
             Sym::Number(n) => write!(f, "{}", n),
             Sym::Keyword(kw) => write!(f, "{}", kw.to_uppercase()),
             Sym::Operator(op) => write!(f, "{}", op),
             Sym::Symbol(sym) => write!(f, "{}", sym),
+            Sym::Comment(comment) => write!(f, "{}", comment),
             Sym::Eof => write!(f, "<eof>"),
         }
     }
@@ -59,12 +67,16 @@ impl Display for Sym<'_> {
 /// # Operator Precedence
 ///
 /// From highest to lowest precedence:
-/// 1. Unary: `+`, `-`, `NOT`
-/// 2. Multiplicative: `*`, `/`
-/// 3. Additive: `+`, `-`
-/// 4. Comparison: `<`, `<=`, `>`, `>=`, `==`, `!=`
-/// 5. Logical: `AND`, `OR`, `XOR`
-#[derive(Clone, Debug, Copy, Serialize)]
+/// 1. Exponentiation: `^` (right-associative)
+/// 2. Unary: `+`, `-`
+/// 3. Multiplicative: `*`, `/`, `%`
+/// 4. Additive: `+`, `-`
+/// 5. Comparison: `<`, `<=`, `>`, `>=`, `==`, `!=`
+/// 6. Negation: `NOT`
+/// 7. Conjunction: `AND`
+/// 8. Exclusive disjunction: `XOR`
+/// 9. Disjunction: `OR`
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Serialize)]
 pub enum Operator {
     /// Addition operator `+`
     Add,
@@ -74,6 +86,10 @@ pub enum Operator {
     Mul,
     /// Division operator `/`
     Div,
+    /// Modulo operator `%`
+    Mod,
+    /// Exponentiation operator `^`
+    Pow,
     /// Equality operator `==`
     Eq,
     /// Inequality operator `!=`
@@ -103,6 +119,8 @@ impl Display for Operator {
             Operator::Sub => write!(f, "-"),
             Operator::Mul => write!(f, "*"),
             Operator::Div => write!(f, "/"),
+            Operator::Mod => write!(f, "%"),
+            Operator::Pow => write!(f, "^"),
             Operator::Eq => write!(f, "=="),
             Operator::Neq => write!(f, "!="),
             Operator::Lt => write!(f, "<"),